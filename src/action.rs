@@ -1,4 +1,8 @@
-use crate::models::{state::StateModel, ChatSession, MsgID, SessionPool};
+use crate::backend::BackendEvent;
+use crate::models::{
+    state::StateModel, ChatSession, Message, MsgID, SearchQuery, SessionPool, SessionRecord,
+    UserState, UsrID,
+};
 
 pub enum SessionsModelAction {
     Reload,
@@ -6,14 +10,82 @@ pub enum SessionsModelAction {
     // cause they are never used by ui components
     Fetch,
     Set(SessionPool),
+    FetchFailed(String),
+    // fetch again after a failure, sent by the backoff timer
+    Retry,
+    MarkRead(ChatSession),
+    // below are pushed by the backend
+    Update(SessionRecord),
+    SetPresence(UsrID, UserState),
+    NewMessage {
+        session: ChatSession,
+        message: Message,
+        unread: bool,
+    },
 }
 
 pub enum MessagesModelAction {
     Reload,
     SetBind(ChatSession),
+    Send(String),
+    // send a message that could not be sent once more
+    Resend(MsgID),
+    // the next message sent answers this one, none to answer nothing
+    ReplyTo(Option<MsgID>),
+    // show the thread started by a message of a group next to the conversation
+    OpenThread(MsgID),
+    CloseThread,
+    SendInThread(String),
+    // put the emoji on the message, or take it off if the user has put it there already
+    React(MsgID, String),
+    // the composer rewrites this message instead of sending a new one, none to stop rewriting
+    Editing(Option<MsgID>),
+    Edit(MsgID, String),
+    Delete(MsgID),
+    // ask the backend for the messages of the bound session matching the query, none to stop
+    Search(Option<SearchQuery>),
+    // the user has scrolled to the top, load the page before the first message
+    FetchOlder,
     // below are private actions
     Fetch,
-    SetMessages(Vec<MsgID>),
+    SetMessages(ChatSession, Vec<Message>),
+    // messages newer than the ones already there
+    MergeMessages(ChatSession, Vec<Message>),
+    // a page of messages older than the ones already there
+    PrependMessages(ChatSession, Vec<Message>),
+    FetchOlderFailed(ChatSession, String),
+    FetchFailed(ChatSession, String),
+    // fetch again after a failure, sent by the backoff timer
+    Retry(ChatSession),
+    // the backend has accepted the message, replace the pending one with what it stored
+    Sent {
+        pending: MsgID,
+        message: Message,
+    },
+    SendFailed {
+        session: ChatSession,
+        pending: MsgID,
+        error: String,
+    },
+    ReactFailed {
+        session: ChatSession,
+        message: MsgID,
+        emoji: String,
+        added: bool,
+        error: String,
+    },
+    // an edit or delete was refused, the message is put back as it was
+    ChangeFailed {
+        session: ChatSession,
+        previous: Message,
+        error: String,
+    },
+    SearchResults(ChatSession, SearchQuery, Vec<Message>),
+    SearchFailed(ChatSession, SearchQuery, String),
+    // a message pushed by the backend
+    Receive(ChatSession, Message),
+    // a message sent before has changed, as told by the backend
+    Update(ChatSession, Message),
 }
 
 pub enum StateModelAction {
@@ -25,6 +97,8 @@ pub enum Action {
     SessionsModel(SessionsModelAction),
     MessagesModel(MessagesModelAction),
     StateModel(StateModelAction),
+    // events pushed by the backend, app dispatches them to the models
+    Backend(BackendEvent),
     Nop,
     Quit,
     MultiAction(Vec<Action>),
@@ -3,8 +3,12 @@ use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 
 use std::rc::Rc;
 
-use crate::action::Action;
-use crate::models::{messages::MessagesModel, sessions::SessionsModel, state::StateModel};
+use crate::action::{Action, MessagesModelAction, SessionsModelAction};
+use crate::backend::{BackendEvent, SharedBackend};
+use crate::models::{
+    messages::MessagesModel, retry::Backoff, sessions::SessionsModel, state::StateModel,
+    store::SharedStore, UsrID,
+};
 use crate::tio::Tio;
 use crate::ui_entities::{
     blueprints::UiBlueprints, root_window::RootWindow, TerminalEventResult, UiEntity, UiMetaData,
@@ -14,22 +18,24 @@ pub struct App {
     shoud_quit: bool,
     action_tx: UnboundedSender<Action>,
     action_rx: UnboundedReceiver<Action>,
+    backend: SharedBackend,
     pub sessions_model: SessionsModel,
     pub messages_model: MessagesModel,
     pub state_model: StateModel,
 }
 
 impl App {
-    pub fn new() -> Result<Self> {
+    pub fn new(backend: SharedBackend, store: SharedStore) -> Result<Self> {
         let (action_tx, action_rx) = tokio::sync::mpsc::unbounded_channel();
 
         Ok(Self {
-            sessions_model: SessionsModel::new(action_tx.clone()),
-            messages_model: MessagesModel::new(action_tx.clone()),
+            sessions_model: SessionsModel::new(action_tx.clone(), backend.clone(), store.clone()),
+            messages_model: MessagesModel::new(action_tx.clone(), backend.clone(), store),
             state_model: StateModel::new(),
             shoud_quit: false,
             action_tx,
             action_rx,
+            backend,
         })
     }
 
@@ -47,6 +53,9 @@ impl App {
             Action::StateModel(act) => {
                 self.state_model.handle_action(act);
             }
+            Action::Backend(event) => {
+                self.handle_backend_event(event);
+            }
             Action::MultiAction(actions) => {
                 for action in actions {
                     self.handle_action(action);
@@ -57,7 +66,67 @@ impl App {
         }
     }
 
+    fn handle_backend_event(&mut self, event: BackendEvent) {
+        match event {
+            BackendEvent::NewMessage(session, message) => {
+                let is_open = self.messages_model.bind.as_ref() == Some(&session);
+                let unread = !is_open && message.from != self.backend.current_user();
+                self.sessions_model
+                    .handle_action(SessionsModelAction::NewMessage {
+                        session: session.clone(),
+                        message: message.clone(),
+                        unread,
+                    });
+                self.messages_model
+                    .handle_action(MessagesModelAction::Receive(session, message));
+            }
+            BackendEvent::MessageUpdated(session, message) => {
+                self.messages_model
+                    .handle_action(MessagesModelAction::Update(session, message));
+            }
+            BackendEvent::SessionUpdated(record) => {
+                self.sessions_model
+                    .handle_action(SessionsModelAction::Update(record));
+            }
+            BackendEvent::PresenceChanged(user, state) => {
+                self.sessions_model
+                    .handle_action(SessionsModelAction::SetPresence(user, state));
+            }
+        }
+    }
+
+    // forward everything the backend pushes into the action channel,
+    // so it is handled in the same place as the actions from the ui
+    fn subscribe_backend_events(&self) {
+        let backend = self.backend.clone();
+        let tx = self.action_tx.clone();
+        tokio::spawn(async move {
+            // without a subscription the app only sees fetched data, so it is tried again
+            let mut backoff = Backoff::default();
+            let mut events = loop {
+                match backend.subscribe().await {
+                    Ok(events) => break events,
+                    Err(_) => match backoff.next_delay() {
+                        Some(delay) => tokio::time::sleep(delay).await,
+                        None => return,
+                    },
+                }
+            };
+            while let Some(event) = events.recv().await {
+                if tx.send(Action::Backend(event)).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+
+    pub fn current_user(&self) -> UsrID {
+        self.backend.current_user()
+    }
+
     pub async fn run(&mut self) -> Result<()> {
+        self.subscribe_backend_events();
+
         let mut tio = Tio::new(4.0, 60.0)?;
         tio.enter()?;
 
@@ -89,13 +158,11 @@ impl App {
 
             // draw ui here
             if root_window.meta_data.get_should_draw() {
-                tio.canvas
-                    .draw(|f| {
-                        let mut ui_blueprints = UiBlueprints::new();
-                        root_window.make_blueprints(f.size(), &mut ui_blueprints, 0);
-                        ui_blueprints.draw(self, f);
-                    })
-                    .unwrap();
+                tio.canvas.draw(|f| {
+                    let mut ui_blueprints = UiBlueprints::new();
+                    root_window.make_blueprints(f.size(), &mut ui_blueprints, 0);
+                    ui_blueprints.draw(self, f);
+                })?;
                 root_window.meta_data.set_should_draw(false);
                 root_window.meta_data.increment_draw_counter();
             }
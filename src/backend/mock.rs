@@ -0,0 +1,399 @@
+use async_trait::async_trait;
+use chrono::{Duration as ChronoDuration, Utc};
+use color_eyre::eyre::{eyre, Result};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::models::{
+    ChatSession, DeliveryStatus, GrpID, Message, MsgID, SearchQuery, SessionPool, SessionRecord,
+    UserState, UsrID,
+};
+
+use super::{BackendEvent, ChatBackend};
+
+const MOCK_USER: &str = "kevin";
+
+#[derive(Default)]
+struct MockState {
+    messages: HashMap<ChatSession, Vec<Message>>,
+    subscribers: Vec<UnboundedSender<BackendEvent>>,
+    sent_counter: usize,
+}
+
+/// In-memory backend with fake data, every call waits `latency` to simulate the network
+#[derive(Default)]
+pub struct MockBackend {
+    latency: Duration,
+    state: Mutex<MockState>,
+}
+
+impl MockBackend {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    pub fn with_latency(self, latency: Duration) -> Self {
+        let mut ret = self;
+        ret.latency = latency;
+        ret
+    }
+
+    async fn simulate_latency(&self) {
+        if !self.latency.is_zero() {
+            tokio::time::sleep(self.latency).await;
+        }
+    }
+
+    // change a stored message and tell the subscribers about it, unless the change is refused
+    async fn update_message(
+        &self,
+        session: ChatSession,
+        id: MsgID,
+        update: impl FnOnce(&mut Message) -> Result<()>,
+    ) -> Result<Message> {
+        self.simulate_latency().await;
+        let mut state = self.state.lock().unwrap();
+        let message = state
+            .messages
+            .entry(session.clone())
+            .or_insert_with_key(Self::seed_messages)
+            .iter_mut()
+            .find(|msg| msg.id == id)
+            .ok_or_else(|| eyre!("no message {}", id.0))?;
+        update(message)?;
+        let message = message.clone();
+        state.subscribers.retain(|tx| {
+            tx.send(BackendEvent::MessageUpdated(
+                session.clone(),
+                message.clone(),
+            ))
+            .is_ok()
+        });
+        Ok(message)
+    }
+
+    fn seed_messages(session: &ChatSession) -> Vec<Message> {
+        let me = UsrID(MOCK_USER.to_string());
+        let seeded = |idx: usize, from: UsrID, content: String, minutes_ago: i64| Message {
+            id: MsgID(format!("seed-{}", idx)),
+            from,
+            to: session.clone().into(),
+            content,
+            sent_at: Utc::now() - ChronoDuration::minutes(minutes_ago),
+            edited_at: None,
+            status: DeliveryStatus::Read,
+            reply_to: None,
+            thread: None,
+            reactions: Vec::new(),
+            history: Vec::new(),
+            deleted: false,
+        };
+
+        match session {
+            ChatSession::WithOther(usr) if usr.0 == "SystemBotRaphina" => {
+                vec![seeded(
+                    0,
+                    usr.clone(),
+                    "Hello, I'm Raphina. I'm a bot.".to_string(),
+                    1,
+                )]
+            }
+            ChatSession::WithOther(usr) => (1..100)
+                .map(|x| {
+                    let from = if x % 2 == 0 { me.clone() } else { usr.clone() };
+                    // now and then a message answers an earlier one
+                    let parent = (x % 7 == 0).then(|| MsgID(format!("seed-{}", x - 5)));
+                    // spread over the last few days
+                    let minutes_ago = (100 - x as i64) * 47;
+                    let mut message =
+                        seeded(x, from, "a".repeat(x * 3), minutes_ago).with_reply_to(parent);
+                    // and some are liked, or were thought over
+                    if x % 6 == 0 {
+                        message.add_reaction("👍", usr.clone());
+                    }
+                    if x % 11 == 0 {
+                        message.edit("b".repeat(x * 2));
+                    }
+                    message
+                })
+                .collect(),
+            ChatSession::Group(_) => {
+                let members = ["Neil", "Alice", MOCK_USER];
+                (1..100)
+                    .map(|x| {
+                        let from = UsrID(members[x % members.len()].to_string());
+                        // a couple of messages get discussed in a thread of their own
+                        let root = match x % 9 {
+                            0 => Some(MsgID(format!("seed-{}", x - 4))),
+                            1 if x > 1 => Some(MsgID(format!("seed-{}", x - 5))),
+                            _ => None,
+                        };
+                        let mut message =
+                            seeded(x, from, "a".repeat(x * 3), 100 - x as i64).with_thread(root);
+                        if x % 5 == 0 {
+                            message.add_reaction("🎉", UsrID(members[(x + 1) % 3].to_string()));
+                            message.add_reaction("🎉", UsrID(members[(x + 2) % 3].to_string()));
+                            message.add_reaction("👀", UsrID(members[x % 3].to_string()));
+                        }
+                        if x % 17 == 0 {
+                            message.delete();
+                        }
+                        message
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl ChatBackend for MockBackend {
+    fn current_user(&self) -> UsrID {
+        UsrID(MOCK_USER.to_string())
+    }
+
+    async fn list_sessions(&self) -> Result<SessionPool> {
+        self.simulate_latency().await;
+        let sessions = [
+            (ChatSession::WithOther(UsrID(String::from("Alice"))), 0),
+            (ChatSession::WithOther(UsrID(String::from("Bob"))), 1),
+            (
+                ChatSession::Group(GrpID(String::from("Nordic Nostalgia"))),
+                5,
+            ),
+        ];
+
+        let mut state = self.state.lock().unwrap();
+        let records = sessions
+            .into_iter()
+            .map(|(session, unread_msg)| {
+                let last_message = state
+                    .messages
+                    .entry(session.clone())
+                    .or_insert_with_key(Self::seed_messages)
+                    .last()
+                    .cloned();
+                SessionRecord {
+                    session,
+                    bookmark: last_message
+                        .as_ref()
+                        .map_or(MsgID(String::from("0")), |msg| msg.id.clone()),
+                    unread_msg,
+                    last_message,
+                }
+            })
+            .collect();
+
+        Ok(SessionPool(records))
+    }
+
+    async fn fetch_messages(&self, session: ChatSession) -> Result<Vec<Message>> {
+        self.simulate_latency().await;
+        let mut state = self.state.lock().unwrap();
+        let messages = state
+            .messages
+            .entry(session)
+            .or_insert_with_key(Self::seed_messages);
+        Ok(messages.clone())
+    }
+
+    async fn fetch_before(
+        &self,
+        session: ChatSession,
+        before: Option<MsgID>,
+        limit: usize,
+    ) -> Result<Vec<Message>> {
+        let messages = self.fetch_messages(session).await?;
+        let end = before
+            .and_then(|id| messages.iter().position(|msg| msg.id == id))
+            .unwrap_or(messages.len());
+        Ok(messages[end.saturating_sub(limit)..end].to_vec())
+    }
+
+    async fn fetch_messages_since(
+        &self,
+        session: ChatSession,
+        after: MsgID,
+    ) -> Result<Vec<Message>> {
+        let messages = self.fetch_messages(session).await?;
+        match messages.iter().position(|msg| msg.id == after) {
+            Some(idx) => Ok(messages[idx + 1..].to_vec()),
+            None => Ok(messages),
+        }
+    }
+
+    async fn send_message(
+        &self,
+        session: ChatSession,
+        content: String,
+        reply_to: Option<MsgID>,
+        thread: Option<MsgID>,
+    ) -> Result<Message> {
+        self.simulate_latency().await;
+        let mut state = self.state.lock().unwrap();
+        state.sent_counter += 1;
+        let mut message = Message::outgoing(
+            MsgID(format!("mock-{}", state.sent_counter)),
+            self.current_user(),
+            session.clone().into(),
+            content,
+        )
+        .with_reply_to(reply_to)
+        .with_thread(thread);
+        message.status = DeliveryStatus::Sent;
+        state
+            .messages
+            .entry(session.clone())
+            .or_insert_with_key(Self::seed_messages)
+            .push(message.clone());
+        // drop subscribers whose receiver has gone away
+        state.subscribers.retain(|tx| {
+            tx.send(BackendEvent::NewMessage(session.clone(), message.clone()))
+                .is_ok()
+        });
+        Ok(message)
+    }
+
+    async fn add_reaction(
+        &self,
+        session: ChatSession,
+        message: MsgID,
+        emoji: String,
+    ) -> Result<Message> {
+        let me = self.current_user();
+        self.update_message(session, message, |msg| {
+            if msg.deleted {
+                return Err(eyre!("message {} is deleted", msg.id.0));
+            }
+            msg.add_reaction(&emoji, me);
+            Ok(())
+        })
+        .await
+    }
+
+    async fn remove_reaction(
+        &self,
+        session: ChatSession,
+        message: MsgID,
+        emoji: String,
+    ) -> Result<Message> {
+        let me = self.current_user();
+        self.update_message(session, message, |msg| {
+            if msg.deleted {
+                return Err(eyre!("message {} is deleted", msg.id.0));
+            }
+            msg.remove_reaction(&emoji, &me);
+            Ok(())
+        })
+        .await
+    }
+
+    async fn edit_message(
+        &self,
+        session: ChatSession,
+        message: MsgID,
+        content: String,
+    ) -> Result<Message> {
+        let me = self.current_user();
+        self.update_message(session, message, |msg| {
+            if msg.from != me || msg.deleted {
+                return Err(eyre!("message {} can not be edited", msg.id.0));
+            }
+            msg.edit(content);
+            Ok(())
+        })
+        .await
+    }
+
+    async fn delete_message(&self, session: ChatSession, message: MsgID) -> Result<Message> {
+        let me = self.current_user();
+        self.update_message(session, message, |msg| {
+            if msg.from != me {
+                return Err(eyre!("message {} can not be deleted", msg.id.0));
+            }
+            msg.delete();
+            Ok(())
+        })
+        .await
+    }
+
+    async fn search(&self, session: ChatSession, query: SearchQuery) -> Result<Vec<Message>> {
+        let matcher = query.matcher().map_err(|err| eyre!(err))?;
+        let messages = self.fetch_messages(session).await?;
+        Ok(messages
+            .into_iter()
+            .filter(|msg| SearchQuery::found_in(&matcher, msg))
+            .collect())
+    }
+
+    async fn subscribe(&self) -> Result<UnboundedReceiver<BackendEvent>> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        // pretend some friends are around
+        let presence = [("Alice", UserState::Online), ("Bob", UserState::Idle)];
+        for (usr, state) in presence {
+            let _ = tx.send(BackendEvent::PresenceChanged(UsrID(usr.to_string()), state));
+        }
+        self.state.lock().unwrap().subscribers.push(tx);
+        Ok(rx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_fetch_seeded_messages() {
+        let backend = MockBackend::new();
+        let bot = ChatSession::WithOther(UsrID("SystemBotRaphina".to_string()));
+        let messages = backend.fetch_messages(bot).await.unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].from, UsrID("SystemBotRaphina".to_string()));
+
+        let alice = ChatSession::WithOther(UsrID("Alice".to_string()));
+        assert_eq!(backend.fetch_messages(alice).await.unwrap().len(), 99);
+        assert_eq!(backend.list_sessions().await.unwrap().0.len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_before_pages_backwards() {
+        let backend = MockBackend::new();
+        let alice = ChatSession::WithOther(UsrID("Alice".to_string()));
+        let ids = |messages: Vec<Message>| messages.into_iter().map(|m| m.id.0).collect::<Vec<_>>();
+
+        let latest = backend.fetch_before(alice.clone(), None, 2).await.unwrap();
+        assert_eq!(ids(latest), vec!["seed-98", "seed-99"]);
+        let older = backend
+            .fetch_before(alice.clone(), Some(MsgID("seed-3".to_string())), 5)
+            .await
+            .unwrap();
+        assert_eq!(ids(older), vec!["seed-1", "seed-2"]);
+    }
+
+    #[tokio::test]
+    async fn test_send_message_is_stored_and_pushed() {
+        let backend = MockBackend::new();
+        let mut events = backend.subscribe().await.unwrap();
+        let bob = ChatSession::WithOther(UsrID("Bob".to_string()));
+
+        let sent = backend
+            .send_message(bob.clone(), "hi bob".to_string(), None, None)
+            .await
+            .unwrap();
+        assert_eq!(sent.from, backend.current_user());
+        assert_eq!(sent.content, "hi bob");
+
+        let messages = backend.fetch_messages(bob.clone()).await.unwrap();
+        assert_eq!(messages.last().map(|m| &m.id), Some(&sent.id));
+        let pushed = std::iter::from_fn(|| events.try_recv().ok()).find_map(|evt| match evt {
+            BackendEvent::NewMessage(session, pushed) => Some((session, pushed)),
+            _ => None,
+        });
+        let (session, pushed) = pushed.unwrap();
+        assert_eq!(session, bob);
+        assert_eq!(pushed.id, sent.id);
+    }
+}
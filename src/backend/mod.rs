@@ -0,0 +1,114 @@
+/// Backend is the module for talking to whatever actually stores the chats.
+/// Models never fabricate data themselves, instead they hold a `SharedBackend`
+/// and call it from a spawned task, then send the result back as an `Action`.
+///
+/// Implementations:
+/// - `MockBackend`: in-memory fake data, used for development and tests.
+/// - `TcpBackend`: talks to `chat-server` with the line protocol in `protocol`.
+pub mod mock;
+pub mod protocol;
+pub mod tcp;
+
+use async_trait::async_trait;
+use color_eyre::eyre::{eyre, Result};
+use tokio::sync::mpsc::UnboundedReceiver;
+
+use std::sync::Arc;
+
+use crate::models::{
+    ChatSession, Message, MsgID, SearchQuery, SessionPool, SessionRecord, UserState, UsrID,
+};
+
+/// Events pushed by the backend without being asked for
+#[derive(Debug, Clone)]
+pub enum BackendEvent {
+    NewMessage(ChatSession, Message),
+    // a session was created or its summary changed
+    SessionUpdated(SessionRecord),
+    // a message sent before has changed, e.g. it was edited or someone reacted to it
+    MessageUpdated(ChatSession, Message),
+    PresenceChanged(UsrID, UserState),
+}
+
+#[async_trait]
+pub trait ChatBackend: Send + Sync {
+    /// The user this backend is logged in as
+    fn current_user(&self) -> UsrID;
+
+    /// All sessions the current user takes part in
+    async fn list_sessions(&self) -> Result<SessionPool>;
+
+    /// Messages of one session, oldest first
+    async fn fetch_messages(&self, session: ChatSession) -> Result<Vec<Message>>;
+
+    /// At most `limit` messages of one session older than `before`, oldest first,
+    /// the newest ones if there is no `before`
+    async fn fetch_before(
+        &self,
+        session: ChatSession,
+        before: Option<MsgID>,
+        limit: usize,
+    ) -> Result<Vec<Message>>;
+
+    /// Messages of one session newer than `after`, oldest first,
+    /// all of them if the backend does not know `after`
+    async fn fetch_messages_since(
+        &self,
+        session: ChatSession,
+        after: MsgID,
+    ) -> Result<Vec<Message>>;
+
+    /// Send a message to the session, in reply to `reply_to` if there is one,
+    /// and into the thread started by `thread` if there is one,
+    /// returns the message as stored by the backend
+    async fn send_message(
+        &self,
+        session: ChatSession,
+        content: String,
+        reply_to: Option<MsgID>,
+        thread: Option<MsgID>,
+    ) -> Result<Message>;
+
+    /// Put `emoji` on a message on behalf of the current user,
+    /// returns the message as stored by the backend
+    async fn add_reaction(
+        &self,
+        session: ChatSession,
+        message: MsgID,
+        emoji: String,
+    ) -> Result<Message>;
+
+    /// Take the `emoji` of the current user off a message,
+    /// returns the message as stored by the backend
+    async fn remove_reaction(
+        &self,
+        session: ChatSession,
+        message: MsgID,
+        emoji: String,
+    ) -> Result<Message>;
+
+    /// Replace what a message of the current user says, the old content is kept in its history,
+    /// returns the message as stored by the backend
+    async fn edit_message(
+        &self,
+        session: ChatSession,
+        message: MsgID,
+        content: String,
+    ) -> Result<Message>;
+
+    /// Delete a message of the current user, it stays in the session as a tombstone,
+    /// returns the tombstone as stored by the backend
+    async fn delete_message(&self, session: ChatSession, message: MsgID) -> Result<Message>;
+
+    /// Messages of one session matching `query`, oldest first, loaded or not,
+    /// a backend that can not search refuses, the app then only searches what it has loaded
+    async fn search(&self, _session: ChatSession, _query: SearchQuery) -> Result<Vec<Message>> {
+        Err(eyre!("searching is not supported"))
+    }
+
+    /// Subscribe to events pushed by the backend,
+    /// the receiver yields nothing after the backend goes away
+    async fn subscribe(&self) -> Result<UnboundedReceiver<BackendEvent>>;
+}
+
+pub type SharedBackend = Arc<dyn ChatBackend>;
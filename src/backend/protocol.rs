@@ -0,0 +1,161 @@
+/// Protocol is the wire format spoken between `TcpBackend` and `chat-server`.
+///
+/// Every frame is one JSON object terminated by a newline (`\n`).
+///
+/// Client to server: a `RequestFrame`, the `id` is picked by the client and echoed back
+/// in the matching reply, so several requests can be in flight at the same time.
+/// ```text
+/// {"id":1,"type":"login","user":"alice"}
+/// {"id":2,"type":"list_sessions"}
+/// {"id":3,"type":"history","session":{"with_other":"bob"},"before":null,"after":"m41","limit":50}
+/// {"id":4,"type":"send","session":{"group":"Nordic Nostalgia"},"content":"hi all","reply_to":"m40"}
+/// {"id":5,"type":"add_reaction","session":{"group":"Nordic Nostalgia"},"message":"m40","emoji":"🎉"}
+/// {"id":6,"type":"remove_reaction","session":{"group":"Nordic Nostalgia"},"message":"m40","emoji":"🎉"}
+/// {"id":7,"type":"edit","session":{"with_other":"bob"},"message":"m42","content":"hi bob!"}
+/// {"id":8,"type":"delete","session":{"with_other":"bob"},"message":"m42"}
+/// {"id":9,"type":"search","session":{"with_other":"bob"},"query":{"text":"lunch","regex":false,"case_sensitive":false}}
+/// ```
+///
+/// Server to client: a `ServerFrame`, which is either the reply to a request,
+/// or an event pushed by the server at any time after login.
+/// ```text
+/// {"frame":"reply","id":1,"response":{"type":"welcome","user":"alice"}}
+/// {"frame":"reply","id":2,"response":{"type":"sessions","sessions":[...]}}
+/// {"frame":"reply","id":3,"response":{"type":"history","messages":[...]}}
+/// {"frame":"reply","id":4,"response":{"type":"sent","message":{...}}}
+/// {"frame":"reply","id":5,"response":{"type":"updated","message":{...}}}
+/// {"frame":"reply","id":6,"response":{"type":"error","reason":"login first"}}
+/// {"frame":"event","event":{"type":"new_message","session":{"with_other":"alice"},"message":{...}}}
+/// {"frame":"event","event":{"type":"session_updated","record":{...}}}
+/// {"frame":"event","event":{"type":"message_updated","session":{"group":"Nordic Nostalgia"},"message":{...}}}
+/// {"frame":"event","event":{"type":"presence_changed","user":"bob","state":"online"}}
+/// ```
+///
+/// Rules:
+/// - `login` must be the first request of a connection, anything else is answered with `error`.
+/// - `history` returns messages oldest first; `before` and `after` are exclusive cursors,
+///   `limit` counts from the newest message before the cursor, `null` means no limit.
+///   An `after` the server does not know is ignored, so the whole range is returned.
+/// - `send` to a user or group the server does not know, or to yourself, is answered with `error`.
+/// - `reply_to` of `send` is optional, it must name a message of the same session.
+/// - `thread` of `send` is optional, it names the first message of a thread in a group session,
+///   which is not in a thread itself.
+/// - `add_reaction` and `remove_reaction` answer with the message as it is now, everybody else
+///   in the session is told with `message_updated`; adding twice or removing what is not there
+///   changes nothing.
+/// - only the author can `edit` or `delete` a message, both answer with the message as it is now
+///   and tell everybody else with `message_updated`. A deleted message keeps its id and place
+///   with `deleted` set and its content, history and reactions dropped, it can not be edited
+///   nor reacted to.
+/// - `search` answers with `history` of the messages whose content matches the query, a `regex`
+///   query that does not compile is answered with `error`. Deleted messages never match.
+/// - sessions in replies and events are always seen from the side of the logged-in user.
+/// - right after login the server pushes `presence_changed` for everyone who is online.
+use serde::{Deserialize, Serialize};
+
+use crate::models::{ChatSession, Message, MsgID, SearchQuery, SessionRecord, UserState, UsrID};
+
+pub type RequestID = u64;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request {
+    Login {
+        user: UsrID,
+    },
+    ListSessions,
+    History {
+        session: ChatSession,
+        before: Option<MsgID>,
+        #[serde(default)]
+        after: Option<MsgID>,
+        limit: Option<usize>,
+    },
+    Send {
+        session: ChatSession,
+        content: String,
+        #[serde(default)]
+        reply_to: Option<MsgID>,
+        #[serde(default)]
+        thread: Option<MsgID>,
+    },
+    AddReaction {
+        session: ChatSession,
+        message: MsgID,
+        emoji: String,
+    },
+    RemoveReaction {
+        session: ChatSession,
+        message: MsgID,
+        emoji: String,
+    },
+    Edit {
+        session: ChatSession,
+        message: MsgID,
+        content: String,
+    },
+    Delete {
+        session: ChatSession,
+        message: MsgID,
+    },
+    Search {
+        session: ChatSession,
+        query: SearchQuery,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RequestFrame {
+    pub id: RequestID,
+    #[serde(flatten)]
+    pub request: Request,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Response {
+    Welcome { user: UsrID },
+    Sessions { sessions: Vec<SessionRecord> },
+    History { messages: Vec<Message> },
+    Sent { message: Message },
+    Updated { message: Message },
+    Error { reason: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Event {
+    NewMessage {
+        session: ChatSession,
+        message: Message,
+    },
+    SessionUpdated {
+        record: SessionRecord,
+    },
+    MessageUpdated {
+        session: ChatSession,
+        message: Message,
+    },
+    PresenceChanged {
+        user: UsrID,
+        state: UserState,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "frame", rename_all = "snake_case")]
+pub enum ServerFrame {
+    Reply { id: RequestID, response: Response },
+    Event { event: Event },
+}
+
+/// Serialize a frame into one line, including the trailing newline
+pub fn encode<T: Serialize>(frame: &T) -> serde_json::Result<String> {
+    let mut line = serde_json::to_string(frame)?;
+    line.push('\n');
+    Ok(line)
+}
+
+pub fn decode<'a, T: Deserialize<'a>>(line: &'a str) -> serde_json::Result<T> {
+    serde_json::from_str(line.trim_end())
+}
@@ -0,0 +1,331 @@
+use async_trait::async_trait;
+use color_eyre::eyre::{eyre, Result};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::sync::oneshot;
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::models::{ChatSession, Message, MsgID, SearchQuery, SessionPool, UsrID};
+
+use super::protocol::{self, Event, Request, RequestFrame, RequestID, Response, ServerFrame};
+use super::{BackendEvent, ChatBackend};
+
+type PendingReplies = Arc<Mutex<ReplyHub>>;
+type Subscribers = Arc<Mutex<EventHub>>;
+
+// a server that does not answer in time is treated like a closed connection
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+// requests waiting for their reply, once the connection is gone nobody will answer them
+#[derive(Default)]
+struct ReplyHub {
+    waiting: HashMap<RequestID, oneshot::Sender<Response>>,
+    closed: bool,
+}
+
+impl ReplyHub {
+    fn wait_for(&mut self, id: RequestID) -> Result<oneshot::Receiver<Response>> {
+        if self.closed {
+            return Err(eyre!("connection to server is closed"));
+        }
+        let (tx, rx) = oneshot::channel();
+        self.waiting.insert(id, tx);
+        Ok(rx)
+    }
+
+    fn reply(&mut self, id: RequestID, response: Response) {
+        if let Some(tx) = self.waiting.remove(&id) {
+            let _ = tx.send(response);
+        }
+    }
+
+    fn close(&mut self) {
+        self.closed = true;
+        self.waiting.clear();
+    }
+}
+
+// events may arrive before anyone subscribed, e.g. presence right after login,
+// they are kept in the backlog and handed to the first subscriber
+#[derive(Default)]
+struct EventHub {
+    senders: Vec<UnboundedSender<BackendEvent>>,
+    backlog: Vec<BackendEvent>,
+    closed: bool,
+}
+
+impl EventHub {
+    fn publish(&mut self, event: BackendEvent) {
+        if self.senders.is_empty() {
+            self.backlog.push(event);
+            return;
+        }
+        self.senders.retain(|tx| tx.send(event.clone()).is_ok());
+    }
+
+    fn subscribe(&mut self) -> UnboundedReceiver<BackendEvent> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        for event in self.backlog.drain(..) {
+            let _ = tx.send(event);
+        }
+        // after the connection is gone, the receiver is closed right away
+        if !self.closed {
+            self.senders.push(tx);
+        }
+        rx
+    }
+
+    fn close(&mut self) {
+        self.closed = true;
+        self.senders.clear();
+    }
+}
+
+/// Backend talking to a `chat-server` over TCP, see `protocol` for the wire format
+pub struct TcpBackend {
+    user: UsrID,
+    next_id: AtomicU64,
+    outgoing: UnboundedSender<RequestFrame>,
+    pending: PendingReplies,
+    subscribers: Subscribers,
+}
+
+impl TcpBackend {
+    /// Connect to the server at `addr` and log in as `user`
+    pub async fn connect(addr: &str, user: UsrID) -> Result<Self> {
+        let stream = TcpStream::connect(addr).await?;
+        let (reader, writer) = stream.into_split();
+        let (outgoing, outgoing_rx) = mpsc::unbounded_channel();
+        let pending = PendingReplies::default();
+        let subscribers = Subscribers::default();
+
+        tokio::spawn(Self::write_frames(writer, outgoing_rx));
+        tokio::spawn(Self::read_frames(
+            reader,
+            pending.clone(),
+            subscribers.clone(),
+        ));
+
+        let backend = Self {
+            user: user.clone(),
+            next_id: AtomicU64::new(0),
+            outgoing,
+            pending,
+            subscribers,
+        };
+
+        match backend.request(Request::Login { user }).await? {
+            Response::Welcome { .. } => Ok(backend),
+            other => Err(eyre!("unexpected reply to login: {:?}", other)),
+        }
+    }
+
+    async fn write_frames(mut writer: OwnedWriteHalf, mut rx: UnboundedReceiver<RequestFrame>) {
+        while let Some(frame) = rx.recv().await {
+            let Ok(line) = protocol::encode(&frame) else {
+                continue;
+            };
+            if writer.write_all(line.as_bytes()).await.is_err() {
+                break;
+            }
+        }
+    }
+
+    // dispatch replies to whoever is waiting for them, and events to the subscribers
+    // when the connection goes away, both the waiting requests and the subscribers see it closed
+    async fn read_frames(reader: OwnedReadHalf, pending: PendingReplies, subscribers: Subscribers) {
+        let mut lines = BufReader::new(reader).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            match protocol::decode::<ServerFrame>(&line) {
+                Ok(ServerFrame::Reply { id, response }) => {
+                    pending.lock().unwrap().reply(id, response);
+                }
+                Ok(ServerFrame::Event { event }) => {
+                    let event = match event {
+                        Event::NewMessage { session, message } => {
+                            BackendEvent::NewMessage(session, message)
+                        }
+                        Event::SessionUpdated { record } => BackendEvent::SessionUpdated(record),
+                        Event::MessageUpdated { session, message } => {
+                            BackendEvent::MessageUpdated(session, message)
+                        }
+                        Event::PresenceChanged { user, state } => {
+                            BackendEvent::PresenceChanged(user, state)
+                        }
+                    };
+                    subscribers.lock().unwrap().publish(event);
+                }
+                // TODO: log the malformed frame
+                Err(_) => {}
+            }
+        }
+        pending.lock().unwrap().close();
+        subscribers.lock().unwrap().close();
+    }
+
+    async fn request(&self, request: Request) -> Result<Response> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let rx = self.pending.lock().unwrap().wait_for(id)?;
+        self.outgoing
+            .send(RequestFrame { id, request })
+            .map_err(|_| eyre!("connection to server is closed"))?;
+
+        match tokio::time::timeout(REQUEST_TIMEOUT, rx).await {
+            Ok(Ok(Response::Error { reason })) => Err(eyre!(reason)),
+            Ok(Ok(response)) => Ok(response),
+            Ok(Err(_)) => Err(eyre!("connection to server is closed")),
+            Err(_) => {
+                self.pending.lock().unwrap().waiting.remove(&id);
+                Err(eyre!("server did not answer in time"))
+            }
+        }
+    }
+
+    async fn history(
+        &self,
+        session: ChatSession,
+        before: Option<MsgID>,
+        after: Option<MsgID>,
+        limit: Option<usize>,
+    ) -> Result<Vec<Message>> {
+        let request = Request::History {
+            session,
+            before,
+            after,
+            limit,
+        };
+        match self.request(request).await? {
+            Response::History { messages } => Ok(messages),
+            other => Err(eyre!("unexpected reply to history: {:?}", other)),
+        }
+    }
+}
+
+#[async_trait]
+impl ChatBackend for TcpBackend {
+    fn current_user(&self) -> UsrID {
+        self.user.clone()
+    }
+
+    async fn list_sessions(&self) -> Result<SessionPool> {
+        match self.request(Request::ListSessions).await? {
+            Response::Sessions { sessions } => Ok(SessionPool(sessions)),
+            other => Err(eyre!("unexpected reply to list_sessions: {:?}", other)),
+        }
+    }
+
+    async fn fetch_messages(&self, session: ChatSession) -> Result<Vec<Message>> {
+        self.history(session, None, None, None).await
+    }
+
+    async fn fetch_before(
+        &self,
+        session: ChatSession,
+        before: Option<MsgID>,
+        limit: usize,
+    ) -> Result<Vec<Message>> {
+        self.history(session, before, None, Some(limit)).await
+    }
+
+    async fn fetch_messages_since(
+        &self,
+        session: ChatSession,
+        after: MsgID,
+    ) -> Result<Vec<Message>> {
+        self.history(session, None, Some(after), None).await
+    }
+
+    async fn send_message(
+        &self,
+        session: ChatSession,
+        content: String,
+        reply_to: Option<MsgID>,
+        thread: Option<MsgID>,
+    ) -> Result<Message> {
+        let request = Request::Send {
+            session,
+            content,
+            reply_to,
+            thread,
+        };
+        match self.request(request).await? {
+            Response::Sent { message } => Ok(message),
+            other => Err(eyre!("unexpected reply to send: {:?}", other)),
+        }
+    }
+
+    async fn add_reaction(
+        &self,
+        session: ChatSession,
+        message: MsgID,
+        emoji: String,
+    ) -> Result<Message> {
+        let request = Request::AddReaction {
+            session,
+            message,
+            emoji,
+        };
+        match self.request(request).await? {
+            Response::Updated { message } => Ok(message),
+            other => Err(eyre!("unexpected reply to add_reaction: {:?}", other)),
+        }
+    }
+
+    async fn remove_reaction(
+        &self,
+        session: ChatSession,
+        message: MsgID,
+        emoji: String,
+    ) -> Result<Message> {
+        let request = Request::RemoveReaction {
+            session,
+            message,
+            emoji,
+        };
+        match self.request(request).await? {
+            Response::Updated { message } => Ok(message),
+            other => Err(eyre!("unexpected reply to remove_reaction: {:?}", other)),
+        }
+    }
+
+    async fn edit_message(
+        &self,
+        session: ChatSession,
+        message: MsgID,
+        content: String,
+    ) -> Result<Message> {
+        let request = Request::Edit {
+            session,
+            message,
+            content,
+        };
+        match self.request(request).await? {
+            Response::Updated { message } => Ok(message),
+            other => Err(eyre!("unexpected reply to edit: {:?}", other)),
+        }
+    }
+
+    async fn delete_message(&self, session: ChatSession, message: MsgID) -> Result<Message> {
+        match self.request(Request::Delete { session, message }).await? {
+            Response::Updated { message } => Ok(message),
+            other => Err(eyre!("unexpected reply to delete: {:?}", other)),
+        }
+    }
+
+    async fn search(&self, session: ChatSession, query: SearchQuery) -> Result<Vec<Message>> {
+        match self.request(Request::Search { session, query }).await? {
+            Response::History { messages } => Ok(messages),
+            other => Err(eyre!("unexpected reply to search: {:?}", other)),
+        }
+    }
+
+    async fn subscribe(&self) -> Result<UnboundedReceiver<BackendEvent>> {
+        Ok(self.subscribers.lock().unwrap().subscribe())
+    }
+}
@@ -0,0 +1,13 @@
+/// A small chat server for development and demos,
+/// see `chat::backend::protocol` for what goes over the wire.
+///
+/// usage: chat-server [addr], addr defaults to 127.0.0.1:7878
+use color_eyre::eyre::Result;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let addr = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| chat::server::DEFAULT_ADDR.to_string());
+    chat::server::run(&addr).await
+}
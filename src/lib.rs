@@ -0,0 +1,8 @@
+pub mod action;
+pub mod app;
+pub mod backend;
+pub mod models;
+pub mod server;
+pub mod tio;
+pub mod ui_entities;
+pub mod widgets;
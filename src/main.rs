@@ -1,12 +1,75 @@
-pub mod action;
-pub mod app;
-pub mod models;
-pub mod tio;
-pub mod ui_entities;
-pub mod widgets;
+use color_eyre::eyre::{eyre, Result};
+
+use std::cell::RefCell;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
+
+use chat::app::App;
+use chat::backend::{mock::MockBackend, tcp::TcpBackend, SharedBackend};
+use chat::models::{store::LocalStore, UsrID};
+use chat::tio::Tio;
+
+const USAGE: &str = "usage: chat [--server <addr>] [--user <name>]";
+
+struct Args {
+    server: Option<String>,
+    user: Option<String>,
+}
+
+fn parse_args() -> Result<Args> {
+    let mut args = Args {
+        server: None,
+        user: None,
+    };
+    let mut raw = std::env::args().skip(1);
+    while let Some(arg) = raw.next() {
+        match arg.as_str() {
+            "--server" => args.server = raw.next(),
+            "--user" => args.user = raw.next(),
+            _ => return Err(eyre!("unknown argument {}\n{}", arg, USAGE)),
+        }
+    }
+    Ok(args)
+}
+
+async fn make_backend(args: &Args) -> Result<SharedBackend> {
+    let Some(ref addr) = args.server else {
+        // no server given, play with the fake data
+        let backend = MockBackend::new().with_latency(Duration::from_secs(3));
+        return Ok(Arc::new(backend));
+    };
+
+    let user = args
+        .user
+        .clone()
+        .or_else(|| std::env::var("USER").ok())
+        .ok_or_else(|| eyre!("no user name given\n{}", USAGE))?;
+    let backend = TcpBackend::connect(addr, UsrID(user)).await?;
+    Ok(Arc::new(backend))
+}
+
+// every server and user has its own history on disk
+fn open_store(args: &Args, backend: &SharedBackend) -> LocalStore {
+    let server = args.server.as_deref().unwrap_or("mock");
+    let account = format!("{}-{}", server, backend.current_user().0);
+    // without a store the app still works, it just starts empty and forgets on exit
+    LocalStore::open(&account).unwrap_or_else(|err| {
+        eprintln!(
+            "history is not saved, the store could not be opened: {}",
+            err
+        );
+        LocalStore::in_memory()
+    })
+}
 
 #[tokio::main]
-async fn main() {
-    let mut app = app::App::new().unwrap();
-    app.run().await.unwrap();
+async fn main() -> Result<()> {
+    color_eyre::install()?;
+    Tio::install_panic_hook();
+    let args = parse_args()?;
+    let backend = make_backend(&args).await?;
+    let store = Rc::new(RefCell::new(open_store(&args, &backend)));
+    let mut app = App::new(backend, store)?;
+    app.run().await
 }
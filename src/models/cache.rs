@@ -0,0 +1,73 @@
+/// Cache keeps the message lists of the sessions visited lately in memory,
+/// so switching back to one of them does not wait for the backend.
+///
+/// It is a small LRU: once `capacity` sessions are cached,
+/// putting another one drops the session that was used the longest time ago.
+use std::collections::{HashMap, VecDeque};
+
+use super::{ChatSession, Message};
+
+pub struct MessageCache {
+    capacity: usize,
+    entries: HashMap<ChatSession, Vec<Message>>,
+    // least recently used first
+    order: VecDeque<ChatSession>,
+}
+
+impl MessageCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// Take the messages of `session` out of the cache
+    pub fn take(&mut self, session: &ChatSession) -> Option<Vec<Message>> {
+        self.order.retain(|s| s != session);
+        self.entries.remove(session)
+    }
+
+    pub fn put(&mut self, session: ChatSession, messages: Vec<Message>) {
+        self.order.retain(|s| *s != session);
+        self.order.push_back(session.clone());
+        self.entries.insert(session, messages);
+
+        while self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.entries.remove(&oldest);
+            }
+        }
+    }
+
+    /// Messages of a cached session, without touching its place in the LRU order
+    pub fn get_mut(&mut self, session: &ChatSession) -> Option<&mut Vec<Message>> {
+        self.entries.get_mut(session)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::UsrID;
+
+    fn session(name: &str) -> ChatSession {
+        ChatSession::WithOther(UsrID(name.to_string()))
+    }
+
+    #[test]
+    fn test_least_recently_used_is_evicted() {
+        let mut cache = MessageCache::new(2);
+        cache.put(session("alice"), vec![]);
+        cache.put(session("bob"), vec![]);
+        // alice is used again, so bob is now the oldest
+        let alice = cache.take(&session("alice")).unwrap();
+        cache.put(session("alice"), alice);
+        cache.put(session("carol"), vec![]);
+
+        assert!(cache.get_mut(&session("bob")).is_none());
+        assert!(cache.get_mut(&session("alice")).is_some());
+        assert!(cache.get_mut(&session("carol")).is_some());
+    }
+}
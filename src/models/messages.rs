@@ -1,48 +1,128 @@
 use tokio::sync::mpsc::UnboundedSender;
 
-use std::time::Duration;
+use std::collections::HashMap;
 
 use crate::action::{Action, MessagesModelAction};
+use crate::backend::SharedBackend;
 
-use super::{ChatSession, MsgID, RemoteData, UsrID};
+use super::cache::MessageCache;
+use super::retry::{self, Backoff};
+use super::store::SharedStore;
+use super::{ChatSession, DeliveryStatus, Message, MsgID, RemoteData, SearchQuery, UsrID};
+
+// how many sessions besides the bound one are kept in memory
+const CACHE_CAPACITY: usize = 8;
+// how many messages are loaded at once, when opening a session or scrolling back
+const PAGE_SIZE: usize = 50;
+
+// loading the history page by page, from the newest message backwards
+#[derive(Default)]
+pub struct Scrollback {
+    pub loading: bool,
+    pub exhausted: bool,
+    pub error: Option<String>,
+}
+
+// the messages the backend found for a query, they may be older than anything loaded
+pub struct Search {
+    pub query: SearchQuery,
+    pub hits: RemoteData<Vec<Message>>,
+}
 
 pub struct MessagesModel {
     pub bind: Option<ChatSession>,
-    pub messages: RemoteData<Vec<MsgID>>,
+    pub messages: RemoteData<Vec<Message>>,
     action_tx: UnboundedSender<Action>,
+    backend: SharedBackend,
+    store: SharedStore,
+    cache: MessageCache,
+    scrollback: Scrollback,
+    backoff: Backoff,
+    pending_counter: usize,
+    // the message the composer is answering
+    replying_to: Option<MsgID>,
+    // the message the composer is rewriting
+    editing: Option<MsgID>,
+    // the first message of the thread shown next to the conversation
+    thread: Option<MsgID>,
+    search: Option<Search>,
+    // why sending or changing a message did not go through
+    failures: HashMap<MsgID, String>,
+    // how many things went wrong so far and the message of the last one, so each is told once
+    last_failure: (usize, Option<MsgID>),
 }
 
 impl MessagesModel {
-    pub fn new(action_tx: UnboundedSender<Action>) -> Self {
-        Self {
+    pub fn new(
+        action_tx: UnboundedSender<Action>,
+        backend: SharedBackend,
+        store: SharedStore,
+    ) -> Self {
+        let mut ret = Self {
             bind: Some(ChatSession::WithOther(UsrID(
                 "SystemBotRaphina".to_string(),
             ))),
             messages: RemoteData::Uninitialized,
             action_tx,
-        }
+            backend,
+            store,
+            cache: MessageCache::new(CACHE_CAPACITY),
+            scrollback: Scrollback::default(),
+            backoff: Backoff::default(),
+            pending_counter: 0,
+            replying_to: None,
+            editing: None,
+            thread: None,
+            search: None,
+            failures: HashMap::new(),
+            last_failure: (0, None),
+        };
+        ret.revalidate();
+        ret
     }
 
-    // TODO: pseudo implementation here
-    pub fn fetch(session: ChatSession) -> Vec<MsgID> {
-        match session {
-            ChatSession::WithOther(usr) if usr.0 == "SystemBotRaphina" => {
-                vec![MsgID("Hello, I'm Raphina. I'm a bot.".to_string())]
-            }
-            _ => (1..100).map(|x| MsgID("a".repeat(x * 3))).collect(),
-        }
+    pub fn get_model_data(&self) -> RemoteData<&Vec<Message>> {
+        self.messages.as_ref()
     }
 
-    pub fn get_model_data(&self) -> RemoteData<&Vec<MsgID>> {
-        match self.messages {
-            RemoteData::Uninitialized => {
-                self.action_tx
-                    .send(Action::MessagesModel(MessagesModelAction::Fetch))
-                    .unwrap();
-                RemoteData::Pending
-            }
-            _ => self.messages.as_ref(),
-        }
+    pub fn get_scrollback(&self) -> &Scrollback {
+        &self.scrollback
+    }
+
+    pub fn get_replying_to(&self) -> Option<&MsgID> {
+        self.replying_to.as_ref()
+    }
+
+    pub fn get_editing(&self) -> Option<&MsgID> {
+        self.editing.as_ref()
+    }
+
+    pub fn get_thread(&self) -> Option<&MsgID> {
+        self.thread.as_ref()
+    }
+
+    pub fn get_search(&self) -> Option<&Search> {
+        self.search.as_ref()
+    }
+
+    /// Why sending or the last change of the message did not go through, if it did not
+    pub fn get_failure(&self, id: &MsgID) -> Option<&String> {
+        self.failures.get(id)
+    }
+
+    /// How many times something went wrong so far, and the message it went wrong with last
+    pub fn get_last_failure(&self) -> (usize, Option<&MsgID>) {
+        (self.last_failure.0, self.last_failure.1.as_ref())
+    }
+
+    /// Why history stopped being saved to disk, if it did
+    pub fn get_store_error(&self) -> Option<String> {
+        self.store.borrow().write_error().map(String::from)
+    }
+
+    /// A loaded message of the bound session
+    pub fn get_message(&self, id: &MsgID) -> Option<&Message> {
+        self.messages.data()?.iter().find(|m| m.id == *id)
     }
 
     pub fn handle_action(&mut self, action: MessagesModelAction) {
@@ -50,47 +130,774 @@ impl MessagesModel {
             MessagesModelAction::Fetch => self.act_on_fetch(),
             MessagesModelAction::Reload => self.act_on_reload(),
             MessagesModelAction::SetBind(v) => self.act_on_set_bind(v),
-            MessagesModelAction::SetMessages(data) => self.act_on_set_messages(data),
+            MessagesModelAction::SetMessages(session, data) => {
+                self.act_on_set_messages(session, data)
+            }
+            MessagesModelAction::MergeMessages(session, data) => {
+                self.act_on_merge_messages(session, data)
+            }
+            MessagesModelAction::FetchFailed(session, error) => {
+                self.act_on_fetch_failed(session, error)
+            }
+            MessagesModelAction::FetchOlder => self.act_on_fetch_older(),
+            MessagesModelAction::PrependMessages(session, data) => {
+                self.act_on_prepend_messages(session, data)
+            }
+            MessagesModelAction::FetchOlderFailed(session, error) => {
+                self.act_on_fetch_older_failed(session, error)
+            }
+            MessagesModelAction::Retry(session) => self.act_on_retry(session),
+            MessagesModelAction::Send(content) => self.act_on_send(content, None),
+            MessagesModelAction::Resend(id) => self.act_on_resend(id),
+            MessagesModelAction::ReplyTo(parent) => self.act_on_reply_to(parent),
+            MessagesModelAction::OpenThread(root) => self.act_on_open_thread(root),
+            MessagesModelAction::CloseThread => self.act_on_close_thread(),
+            MessagesModelAction::SendInThread(content) => self.act_on_send_in_thread(content),
+            MessagesModelAction::Sent { pending, message } => self.act_on_sent(pending, message),
+            MessagesModelAction::SendFailed {
+                session,
+                pending,
+                error,
+            } => self.act_on_send_failed(session, pending, error),
+            MessagesModelAction::React(id, emoji) => self.act_on_react(id, emoji),
+            MessagesModelAction::ReactFailed {
+                session,
+                message,
+                emoji,
+                added,
+                error,
+            } => self.act_on_react_failed(session, message, emoji, added, error),
+            MessagesModelAction::Editing(target) => self.act_on_editing(target),
+            MessagesModelAction::Edit(id, content) => self.act_on_edit(id, content),
+            MessagesModelAction::Delete(id) => self.act_on_delete(id),
+            MessagesModelAction::ChangeFailed {
+                session,
+                previous,
+                error,
+            } => self.act_on_change_failed(session, previous, error),
+            MessagesModelAction::Search(query) => self.act_on_search(query),
+            MessagesModelAction::SearchResults(session, query, hits) => {
+                self.act_on_search_results(session, query, RemoteData::Success(hits))
+            }
+            MessagesModelAction::SearchFailed(session, query, error) => {
+                let failed = RemoteData::Failed {
+                    last_good: None,
+                    error,
+                };
+                self.act_on_search_results(session, query, failed)
+            }
+            MessagesModelAction::Receive(session, message) => self.act_on_receive(session, message),
+            MessagesModelAction::Update(session, message) => self.act_on_update(session, message),
+        }
+    }
+
+    // messages of the bound session, or of a cached one
+    fn messages_of(&mut self, session: &ChatSession) -> Option<&mut Vec<Message>> {
+        if self.bind.as_ref() == Some(session) {
+            self.messages.data_mut()
+        } else {
+            self.cache.get_mut(session)
+        }
+    }
+
+    // keep showing what we have, with the history on disk as the fallback,
+    // while the fetch reconciles it with the backend
+    fn revalidate(&mut self) {
+        if self.messages.data().is_none() {
+            self.messages = self.cached();
         }
+        self.messages = std::mem::take(&mut self.messages).into_refreshing();
+        // the receiver only goes away when the app quits
+        let _ = self
+            .action_tx
+            .send(Action::MessagesModel(MessagesModelAction::Fetch));
     }
 
+    // asked by the user, so start over with the retries as well
     fn act_on_reload(&mut self) {
-        // TODO: maybe reuse the garbage here, not drop vec entirely, keep the space
-        // currently I just keep things simple
-        self.messages = RemoteData::Pending;
-        self.action_tx
-            .send(Action::MessagesModel(MessagesModelAction::Fetch))
-            .unwrap();
+        self.backoff.reset();
+        self.revalidate();
     }
 
+    // the messages of the session we leave go into the cache,
+    // the ones of the session we switch to come out of it, if they are there
     fn act_on_set_bind(&mut self, session: ChatSession) {
-        self.bind = Some(session);
-        // set to pending is not always the solution
-        // sometimes, there may have cache to use
-        self.messages = RemoteData::Pending;
-        self.action_tx
-            .send(Action::MessagesModel(MessagesModelAction::Fetch))
-            .unwrap();
+        if self.bind.as_ref() != Some(&session) {
+            let old = std::mem::take(&mut self.messages).into_data();
+            if let (Some(bind), Some(old)) = (self.bind.take(), old) {
+                self.cache.put(bind, old);
+            }
+            self.messages = match self.cache.take(&session) {
+                Some(messages) => RemoteData::Success(messages),
+                None => RemoteData::Uninitialized,
+            };
+            self.scrollback = Scrollback::default();
+            self.replying_to = None;
+            self.editing = None;
+            self.thread = None;
+            self.search = None;
+            self.bind = Some(session);
+        }
+        self.backoff.reset();
+        self.revalidate();
     }
 
+    fn act_on_retry(&mut self, session: ChatSession) {
+        // the user has moved on to another session meanwhile
+        if self.bind.as_ref() == Some(&session) && self.messages.error().is_some() {
+            self.revalidate();
+        }
+    }
+
+    // with messages at hand the last page of them is fetched again along with the newer ones,
+    // so what was edited, deleted or reacted to meanwhile is reconciled as well
     fn act_on_fetch(&self) {
-        // TODO: DEBUG HERE
-        assert!(self.bind.is_some());
-        if let Some(session) = self.bind.clone() {
-            let _tx = self.action_tx.clone();
-            tokio::spawn(async move {
-                // TODO: fetch will be a async funtion, currently we use sleep to simulate
-                tokio::time::sleep(Duration::from_secs(3)).await;
-                let data = Self::fetch(session);
-                _tx.send(Action::MessagesModel(MessagesModelAction::SetMessages(
-                    data,
-                )))
-                .unwrap();
-            });
-        }
-    }
-
-    fn act_on_set_messages(&mut self, data: Vec<MsgID>) {
-        self.messages = RemoteData::Success(data);
+        let Some(session) = self.bind.clone() else {
+            return;
+        };
+        let last_seen = self.messages.data().and_then(|messages| {
+            messages
+                .iter()
+                .rev()
+                .filter(|m| !is_unconfirmed(m))
+                .nth(PAGE_SIZE)
+                .map(|m| m.id.clone())
+        });
+
+        let _tx = self.action_tx.clone();
+        let backend = self.backend.clone();
+        tokio::spawn(async move {
+            let fetched = match last_seen {
+                Some(after) => backend
+                    .fetch_messages_since(session.clone(), after)
+                    .await
+                    .map(|data| MessagesModelAction::MergeMessages(session.clone(), data)),
+                None => backend
+                    .fetch_before(session.clone(), None, PAGE_SIZE)
+                    .await
+                    .map(|data| MessagesModelAction::SetMessages(session.clone(), data)),
+            };
+            let action = fetched
+                .unwrap_or_else(|err| MessagesModelAction::FetchFailed(session, err.to_string()));
+            let _ = _tx.send(Action::MessagesModel(action));
+        });
+    }
+
+    // cached history of the bound session
+    fn cached(&self) -> RemoteData<Vec<Message>> {
+        let store = self.store.borrow();
+        match self.bind.as_ref().and_then(|s| store.messages(s)) {
+            Some(messages) => RemoteData::Success(messages.clone()),
+            None => RemoteData::Uninitialized,
+        }
+    }
+
+    // the page takes the place of what was there, our messages still on their way are kept
+    fn act_on_set_messages(&mut self, session: ChatSession, data: Vec<Message>) {
+        self.store
+            .borrow_mut()
+            .put_messages(session.clone(), data.clone());
+        let with_unconfirmed = |old: Vec<Message>| {
+            let unconfirmed = old
+                .into_iter()
+                .filter(|m| is_unconfirmed(m) && data.iter().all(|new| new.id != m.id))
+                .collect::<Vec<_>>();
+            [data.clone(), unconfirmed].concat()
+        };
+        // the user may have switched to another session while fetching
+        if self.bind.as_ref() == Some(&session) {
+            self.backoff.reset();
+            self.scrollback.exhausted = data.len() < PAGE_SIZE;
+            let old = std::mem::take(&mut self.messages)
+                .into_data()
+                .unwrap_or_default();
+            self.messages = RemoteData::Success(with_unconfirmed(old));
+        } else if let Some(messages) = self.cache.get_mut(&session) {
+            *messages = with_unconfirmed(std::mem::take(messages));
+        }
+    }
+
+    fn act_on_merge_messages(&mut self, session: ChatSession, data: Vec<Message>) {
+        let mut store = self.store.borrow_mut();
+        for message in data.iter() {
+            // most of the messages fetched again have not changed
+            if store
+                .messages(&session)
+                .is_some_and(|stored| stored.contains(message))
+            {
+                continue;
+            }
+            store.put_message(session.clone(), message.clone());
+        }
+        drop(store);
+
+        if self.bind.as_ref() != Some(&session) {
+            if let Some(messages) = self.cache.get_mut(&session) {
+                data.into_iter().for_each(|m| upsert(messages, m));
+            }
+            return;
+        }
+        self.backoff.reset();
+        let mut messages = std::mem::take(&mut self.messages)
+            .into_data()
+            .unwrap_or_default();
+        data.into_iter().for_each(|m| upsert(&mut messages, m));
+        self.messages = RemoteData::Success(messages);
+    }
+
+    fn act_on_fetch_older(&mut self) {
+        let Some(session) = self.bind.clone() else {
+            return;
+        };
+        if self.scrollback.loading || self.scrollback.exhausted {
+            return;
+        }
+        // nothing on screen yet, the first page is on its way
+        let Some(first) = self.messages.data().and_then(|m| m.first()) else {
+            return;
+        };
+
+        let before = first.id.clone();
+        self.scrollback.loading = true;
+        self.scrollback.error = None;
+        let _tx = self.action_tx.clone();
+        let backend = self.backend.clone();
+        tokio::spawn(async move {
+            let action = match backend
+                .fetch_before(session.clone(), Some(before), PAGE_SIZE)
+                .await
+            {
+                Ok(data) => MessagesModelAction::PrependMessages(session, data),
+                Err(err) => MessagesModelAction::FetchOlderFailed(session, err.to_string()),
+            };
+            let _ = _tx.send(Action::MessagesModel(action));
+        });
+    }
+
+    fn act_on_prepend_messages(&mut self, session: ChatSession, data: Vec<Message>) {
+        if self.bind.as_ref() != Some(&session) {
+            return;
+        }
+        self.scrollback.loading = false;
+        self.scrollback.exhausted = data.len() < PAGE_SIZE;
+        let Some(messages) = self.messages.data_mut() else {
+            return;
+        };
+
+        let older = data
+            .into_iter()
+            .filter(|m| messages.iter().all(|old| old.id != m.id))
+            .collect::<Vec<_>>();
+        messages.splice(0..0, older.clone());
+        // only the page goes to disk, not the whole history again
+        self.store.borrow_mut().put_older_messages(session, older);
+    }
+
+    fn act_on_fetch_older_failed(&mut self, session: ChatSession, error: String) {
+        if self.bind.as_ref() == Some(&session) {
+            self.scrollback.loading = false;
+            self.scrollback.error = Some(error);
+        }
+    }
+
+    fn act_on_fetch_failed(&mut self, session: ChatSession, error: String) {
+        if self.bind.as_ref() != Some(&session) {
+            return;
+        }
+        self.messages = std::mem::take(&mut self.messages).into_failed(error);
+        if let Some(delay) = self.backoff.next_delay() {
+            let action = Action::MessagesModel(MessagesModelAction::Retry(session));
+            retry::send_after(self.action_tx.clone(), delay, action);
+        }
+    }
+
+    // the composer either answers a message or rewrites one
+    fn act_on_reply_to(&mut self, parent: Option<MsgID>) {
+        if parent.is_some() {
+            self.editing = None;
+        }
+        self.replying_to = parent;
+    }
+
+    fn act_on_editing(&mut self, target: Option<MsgID>) {
+        if target.is_some() {
+            self.replying_to = None;
+        }
+        self.editing = target;
+    }
+
+    // a loaded message of the user the backend knows and that is not deleted, it may be changed
+    fn own_message_mut(&mut self, id: &MsgID) -> Option<&mut Message> {
+        let me = self.backend.current_user();
+        self.messages
+            .data_mut()?
+            .iter_mut()
+            .find(|m| m.id == *id)
+            .filter(|m| m.from == me && !m.deleted && !is_unconfirmed(m))
+    }
+
+    // like a reaction, the edit shows up right away and is taken back if the backend refuses it
+    fn act_on_edit(&mut self, id: MsgID, content: String) {
+        self.editing = None;
+        let Some(session) = self.bind.clone() else {
+            return;
+        };
+        let Some(message) = self.own_message_mut(&id) else {
+            return;
+        };
+        if message.content == content {
+            return;
+        }
+        let previous = message.clone();
+        message.edit(content.clone());
+
+        let _tx = self.action_tx.clone();
+        let backend = self.backend.clone();
+        tokio::spawn(async move {
+            let action = match backend.edit_message(session.clone(), id, content).await {
+                Ok(message) => MessagesModelAction::Update(session, message),
+                Err(err) => MessagesModelAction::ChangeFailed {
+                    session,
+                    previous,
+                    error: err.to_string(),
+                },
+            };
+            let _ = _tx.send(Action::MessagesModel(action));
+        });
+    }
+
+    fn act_on_delete(&mut self, id: MsgID) {
+        if self.editing.as_ref() == Some(&id) {
+            self.editing = None;
+        }
+        let Some(session) = self.bind.clone() else {
+            return;
+        };
+        let Some(message) = self.own_message_mut(&id) else {
+            return;
+        };
+        let previous = message.clone();
+        message.delete();
+
+        let _tx = self.action_tx.clone();
+        let backend = self.backend.clone();
+        tokio::spawn(async move {
+            let action = match backend.delete_message(session.clone(), id).await {
+                Ok(message) => MessagesModelAction::Update(session, message),
+                Err(err) => MessagesModelAction::ChangeFailed {
+                    session,
+                    previous,
+                    error: err.to_string(),
+                },
+            };
+            let _ = _tx.send(Action::MessagesModel(action));
+        });
+    }
+
+    fn act_on_change_failed(&mut self, session: ChatSession, previous: Message, error: String) {
+        let id = previous.id.clone();
+        if let Some(message) = self
+            .messages_of(&session)
+            .and_then(|messages| messages.iter_mut().find(|m| m.id == previous.id))
+        {
+            *message = previous;
+        }
+        self.fail(id, error);
+    }
+
+    fn act_on_search(&mut self, query: Option<SearchQuery>) {
+        let (Some(session), Some(query)) = (self.bind.clone(), query) else {
+            self.search = None;
+            return;
+        };
+        self.search = Some(Search {
+            query: query.clone(),
+            hits: RemoteData::Pending,
+        });
+        let _tx = self.action_tx.clone();
+        let backend = self.backend.clone();
+        tokio::spawn(async move {
+            let action = match backend.search(session.clone(), query.clone()).await {
+                Ok(hits) => MessagesModelAction::SearchResults(session, query, hits),
+                Err(err) => MessagesModelAction::SearchFailed(session, query, err.to_string()),
+            };
+            let _ = _tx.send(Action::MessagesModel(action));
+        });
+    }
+
+    // results of an older query or of another session are of no use anymore
+    fn act_on_search_results(
+        &mut self,
+        session: ChatSession,
+        query: SearchQuery,
+        hits: RemoteData<Vec<Message>>,
+    ) {
+        if self.bind.as_ref() != Some(&session) {
+            return;
+        }
+        if let Some(search) = self.search.as_mut().filter(|s| s.query == query) {
+            search.hits = hits;
+        }
+    }
+
+    // only groups have threads
+    fn act_on_open_thread(&mut self, root: MsgID) {
+        if matches!(self.bind, Some(ChatSession::Group(_))) {
+            self.thread = Some(root);
+        }
+    }
+
+    fn act_on_close_thread(&mut self) {
+        self.thread = None;
+    }
+
+    fn act_on_send_in_thread(&mut self, content: String) {
+        if let Some(root) = self.thread.clone() {
+            self.act_on_send(content, Some(root));
+        }
+    }
+
+    fn act_on_send(&mut self, content: String, thread: Option<MsgID>) {
+        // the message being replied to is the one of the conversation's composer
+        let reply_to = match thread {
+            Some(_) => None,
+            None => self.replying_to.take(),
+        };
+        self.send(content, reply_to, thread);
+    }
+
+    // the failed message makes way for a new one, which goes to the end of the conversation
+    fn act_on_resend(&mut self, id: MsgID) {
+        let Some(messages) = self.messages.data_mut() else {
+            return;
+        };
+        let Some(idx) = messages
+            .iter()
+            .position(|m| m.id == id && m.status == DeliveryStatus::Failed)
+        else {
+            return;
+        };
+        let failed = messages.remove(idx);
+        self.failures.remove(&id);
+        self.send(failed.content, failed.reply_to, failed.thread);
+    }
+
+    // the message is appended right away with a pending id,
+    // it gets replaced once the backend has accepted the message
+    fn send(&mut self, content: String, reply_to: Option<MsgID>, thread: Option<MsgID>) {
+        let Some(session) = self.bind.clone() else {
+            return;
+        };
+
+        self.pending_counter += 1;
+        let pending = MsgID(format!("pending-{}", self.pending_counter));
+        if let Some(messages) = self.messages.data_mut() {
+            messages.push(
+                Message::outgoing(
+                    pending.clone(),
+                    self.backend.current_user(),
+                    session.clone().into(),
+                    content.clone(),
+                )
+                .with_reply_to(reply_to.clone())
+                .with_thread(thread.clone()),
+            );
+        }
+
+        let _tx = self.action_tx.clone();
+        let backend = self.backend.clone();
+        tokio::spawn(async move {
+            let action = match backend
+                .send_message(session.clone(), content, reply_to, thread)
+                .await
+            {
+                Ok(message) => MessagesModelAction::Sent { pending, message },
+                Err(err) => MessagesModelAction::SendFailed {
+                    session,
+                    pending,
+                    error: err.to_string(),
+                },
+            };
+            let _ = _tx.send(Action::MessagesModel(action));
+        });
+    }
+
+    fn act_on_sent(&mut self, pending: MsgID, mut message: Message) {
+        if message.status == DeliveryStatus::Sending {
+            message.status = DeliveryStatus::Sent;
+        }
+        let session = message.session_for(&self.backend.current_user());
+        self.store
+            .borrow_mut()
+            .put_message(session.clone(), message.clone());
+
+        if let Some(messages) = self.messages_of(&session) {
+            // the backend may have pushed the message already
+            if messages.iter().any(|m| m.id == message.id) {
+                messages.retain(|m| m.id != pending);
+                return;
+            }
+            if let Some(msg) = messages.iter_mut().find(|m| m.id == pending) {
+                *msg = message;
+            }
+        }
+    }
+
+    fn act_on_send_failed(&mut self, session: ChatSession, pending: MsgID, error: String) {
+        if let Some(messages) = self.messages_of(&session) {
+            if let Some(msg) = messages.iter_mut().find(|m| m.id == pending) {
+                msg.status = DeliveryStatus::Failed;
+            }
+        }
+        self.fail(pending, error);
+    }
+
+    fn fail(&mut self, id: MsgID, error: String) {
+        self.failures.insert(id.clone(), error);
+        self.last_failure = (self.last_failure.0 + 1, Some(id));
+    }
+
+    // the reaction shows up right away, and is taken back if the backend refuses it
+    fn act_on_react(&mut self, id: MsgID, emoji: String) {
+        let Some(session) = self.bind.clone() else {
+            return;
+        };
+        let me = self.backend.current_user();
+        let Some(message) = self
+            .messages
+            .data_mut()
+            .and_then(|messages| messages.iter_mut().find(|m| m.id == id))
+        else {
+            return;
+        };
+        // the backend does not know the message yet
+        if is_unconfirmed(message) {
+            return;
+        }
+        let add = !message.reacted(&emoji, &me);
+        if add {
+            message.add_reaction(&emoji, me);
+        } else {
+            message.remove_reaction(&emoji, &me);
+        }
+
+        let _tx = self.action_tx.clone();
+        let backend = self.backend.clone();
+        tokio::spawn(async move {
+            let result = if add {
+                backend
+                    .add_reaction(session.clone(), id.clone(), emoji.clone())
+                    .await
+            } else {
+                backend
+                    .remove_reaction(session.clone(), id.clone(), emoji.clone())
+                    .await
+            };
+            let action = match result {
+                Ok(message) => MessagesModelAction::Update(session, message),
+                Err(err) => MessagesModelAction::ReactFailed {
+                    session,
+                    message: id,
+                    emoji,
+                    added: add,
+                    error: err.to_string(),
+                },
+            };
+            let _ = _tx.send(Action::MessagesModel(action));
+        });
+    }
+
+    fn act_on_react_failed(
+        &mut self,
+        session: ChatSession,
+        id: MsgID,
+        emoji: String,
+        added: bool,
+        error: String,
+    ) {
+        let me = self.backend.current_user();
+        if let Some(message) = self
+            .messages_of(&session)
+            .and_then(|messages| messages.iter_mut().find(|m| m.id == id))
+        {
+            if added {
+                message.remove_reaction(&emoji, &me);
+            } else {
+                message.add_reaction(&emoji, me);
+            }
+        }
+        self.fail(id, error);
+    }
+
+    // only a message at hand is changed, one not loaded comes in as it is now when it is fetched
+    fn act_on_update(&mut self, session: ChatSession, message: Message) {
+        let Some(old) = self
+            .messages_of(&session)
+            .and_then(|messages| messages.iter_mut().find(|m| m.id == message.id))
+        else {
+            return;
+        };
+        *old = message.clone();
+        // a change that went through makes up for one that did not
+        self.failures.remove(&message.id);
+        self.store.borrow_mut().put_message(session, message);
+    }
+
+    fn act_on_receive(&mut self, session: ChatSession, message: Message) {
+        self.store
+            .borrow_mut()
+            .put_message(session.clone(), message.clone());
+        if let Some(messages) = self.messages_of(&session) {
+            upsert(messages, message);
+        }
+    }
+}
+
+// sent by us, but not accepted by the backend (yet)
+fn is_unconfirmed(message: &Message) -> bool {
+    matches!(
+        message.status,
+        DeliveryStatus::Sending | DeliveryStatus::Failed
+    )
+}
+
+// replace the message with the same id, or add it before our own unconfirmed messages
+fn upsert(messages: &mut Vec<Message>, message: Message) {
+    if let Some(old) = messages.iter_mut().find(|m| m.id == message.id) {
+        *old = message;
+        return;
+    }
+    let idx = messages
+        .iter()
+        .rposition(|m| !is_unconfirmed(m))
+        .map_or(0, |idx| idx + 1);
+    messages.insert(idx, message);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::mock::MockBackend;
+    use crate::models::{fixtures::message, store::LocalStore};
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn test_fetched_page_keeps_messages_on_their_way() {
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        let store = Rc::new(RefCell::new(LocalStore::in_memory()));
+        let mut model = MessagesModel::new(tx, Arc::new(MockBackend::new()), store);
+        let session = model.bind.clone().unwrap();
+
+        let page = vec![message("1", "alice", "hi"), message("2", "alice", "yo")];
+        model.handle_action(MessagesModelAction::SetMessages(session.clone(), page));
+        model.handle_action(MessagesModelAction::Send(String::from("still sending")));
+
+        // the page fetched again has an edit in it, and knows nothing of what we are sending
+        let mut edited = message("2", "alice", "yo");
+        edited.edit(String::from("yo!"));
+        let page = vec![message("1", "alice", "hi"), edited];
+        model.handle_action(MessagesModelAction::SetMessages(session, page));
+
+        let messages = model.get_model_data().data().copied().unwrap();
+        let contents = messages
+            .iter()
+            .map(|m| m.content.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(contents, vec!["hi", "yo!", "still sending"]);
+        assert_eq!(messages[2].status, DeliveryStatus::Sending);
+    }
+
+    #[tokio::test]
+    async fn test_failed_message_is_sent_again() {
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        let store = Rc::new(RefCell::new(LocalStore::in_memory()));
+        let mut model = MessagesModel::new(tx, Arc::new(MockBackend::new()), store);
+        let session = model.bind.clone().unwrap();
+
+        let page = vec![message("1", "alice", "hi")];
+        model.handle_action(MessagesModelAction::SetMessages(session.clone(), page));
+        model.handle_action(MessagesModelAction::Send(String::from("anyone?")));
+        let pending = MsgID(String::from("pending-1"));
+        model.handle_action(MessagesModelAction::SendFailed {
+            session,
+            pending: pending.clone(),
+            error: String::from("connection to server is closed"),
+        });
+        assert_eq!(model.get_last_failure(), (1, Some(&pending)));
+        assert_eq!(
+            model.get_failure(&pending).map(String::as_str),
+            Some("connection to server is closed")
+        );
+
+        model.handle_action(MessagesModelAction::Resend(pending.clone()));
+        assert_eq!(model.get_failure(&pending), None);
+        let messages = model.get_model_data().data().copied().unwrap();
+        let last = messages.last().unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(last.content, "anyone?");
+        assert_eq!(last.status, DeliveryStatus::Sending);
+        assert_ne!(last.id, pending);
+    }
+
+    #[tokio::test]
+    async fn test_refused_change_is_undone_with_its_reason() {
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        let store = Rc::new(RefCell::new(LocalStore::in_memory()));
+        let mut model = MessagesModel::new(tx, Arc::new(MockBackend::new()), store);
+        let session = model.bind.clone().unwrap();
+
+        let mut edited = message("1", "alice", "hi");
+        edited.edit(String::from("hello"));
+        let page = vec![edited];
+        model.handle_action(MessagesModelAction::SetMessages(session.clone(), page));
+        let id = MsgID(String::from("1"));
+        model.handle_action(MessagesModelAction::ChangeFailed {
+            session: session.clone(),
+            previous: message("1", "alice", "hi"),
+            error: String::from("message 1 can not be edited"),
+        });
+        assert_eq!(model.get_message(&id).unwrap().content, "hi");
+        assert_eq!(model.get_last_failure(), (1, Some(&id)));
+        assert!(model.get_failure(&id).is_some());
+
+        // a later change that goes through clears the reason
+        model.handle_action(MessagesModelAction::Update(
+            session,
+            message("1", "alice", "hi!"),
+        ));
+        assert_eq!(model.get_failure(&id), None);
+    }
+
+    #[tokio::test]
+    async fn test_refused_reaction_is_taken_back_with_its_reason() {
+        let (tx, _rx) = tokio::sync::mpsc::unbounded_channel();
+        let store = Rc::new(RefCell::new(LocalStore::in_memory()));
+        let mut model = MessagesModel::new(tx, Arc::new(MockBackend::new()), store);
+        let session = model.bind.clone().unwrap();
+        let me = model.backend.current_user();
+
+        let mut reacted = message("1", "alice", "hi");
+        reacted.add_reaction("👍", me.clone());
+        model.handle_action(MessagesModelAction::SetMessages(
+            session.clone(),
+            vec![reacted],
+        ));
+        let id = MsgID(String::from("1"));
+        model.handle_action(MessagesModelAction::ReactFailed {
+            session,
+            message: id.clone(),
+            emoji: String::from("👍"),
+            added: true,
+            error: String::from("message 1 is deleted"),
+        });
+        assert!(!model.get_message(&id).unwrap().reacted("👍", &me));
+        assert_eq!(model.get_last_failure(), (1, Some(&id)));
+        assert_eq!(
+            model.get_failure(&id).map(String::as_str),
+            Some("message 1 is deleted")
+        );
     }
 }
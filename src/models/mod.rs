@@ -2,10 +2,19 @@
 /// Basic types includes: User, Message, Group
 ///
 ///
+use chrono::{DateTime, Utc};
+use fancy_regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+pub mod cache;
 pub mod messages;
+pub mod retry;
 pub mod sessions;
 pub mod state;
+pub mod store;
 
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum UserState {
     Online,
     Offline,
@@ -14,17 +23,28 @@ pub enum UserState {
     Cusomized(String),
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum Receiver {
     Individual(UsrID),
     Group(GrpID),
 }
 
-#[derive(Debug, Clone)]
-pub struct UsrID(String);
-#[derive(Debug, Clone)]
-pub struct GrpID(String);
-#[derive(Debug, Clone)]
-pub struct MsgID(String);
+impl From<ChatSession> for Receiver {
+    fn from(session: ChatSession) -> Self {
+        match session {
+            ChatSession::WithOther(usr) => Receiver::Individual(usr),
+            ChatSession::Group(grp) => Receiver::Group(grp),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct UsrID(pub String);
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct GrpID(pub String);
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct MsgID(pub String);
 
 struct User {
     pub id: UsrID,
@@ -46,12 +66,163 @@ pub struct Group {
     group_name: String,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    // not yet accepted by the backend
+    Sending,
+    Sent,
+    Delivered,
+    Read,
+    Failed,
+}
+
+// one emoji put on a message, and everyone who put it there, in the order they did
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Reaction {
+    pub emoji: String,
+    pub users: Vec<UsrID>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Message {
-    id: MsgID,
-    from: UsrID,
-    to: Receiver,
-    content: String,
-    // TODO: time, content
+    pub id: MsgID,
+    pub from: UsrID,
+    pub to: Receiver,
+    pub content: String,
+    pub sent_at: DateTime<Utc>,
+    pub edited_at: Option<DateTime<Utc>>,
+    pub status: DeliveryStatus,
+    // the message this one answers, it may be older than anything loaded
+    #[serde(default)]
+    pub reply_to: Option<MsgID>,
+    // the first message of the thread this one is posted in, only groups have threads
+    #[serde(default)]
+    pub thread: Option<MsgID>,
+    // in the order the emojis were first put on the message
+    #[serde(default)]
+    pub reactions: Vec<Reaction>,
+    // what the message said before each edit, oldest first
+    #[serde(default)]
+    pub history: Vec<String>,
+    // a deleted message keeps its place in the conversation, but nothing of what it said
+    #[serde(default)]
+    pub deleted: bool,
+}
+
+impl Message {
+    /// A message written by `from` right now, it has not been seen by the backend yet
+    pub fn outgoing(id: MsgID, from: UsrID, to: Receiver, content: String) -> Self {
+        Self {
+            id,
+            from,
+            to,
+            content,
+            sent_at: Utc::now(),
+            edited_at: None,
+            status: DeliveryStatus::Sending,
+            reply_to: None,
+            thread: None,
+            reactions: Vec::new(),
+            history: Vec::new(),
+            deleted: false,
+        }
+    }
+
+    pub fn with_reply_to(self, parent: Option<MsgID>) -> Self {
+        let mut ret = self;
+        ret.reply_to = parent;
+        ret
+    }
+
+    pub fn with_thread(self, root: Option<MsgID>) -> Self {
+        let mut ret = self;
+        ret.thread = root;
+        ret
+    }
+
+    /// Whether `user` has put `emoji` on this message
+    pub fn reacted(&self, emoji: &str, user: &UsrID) -> bool {
+        self.reactions
+            .iter()
+            .any(|r| r.emoji == emoji && r.users.contains(user))
+    }
+
+    /// Put `emoji` on this message on behalf of `user`, once
+    pub fn add_reaction(&mut self, emoji: &str, user: UsrID) {
+        match self.reactions.iter_mut().find(|r| r.emoji == emoji) {
+            Some(reaction) if reaction.users.contains(&user) => {}
+            Some(reaction) => reaction.users.push(user),
+            None => self.reactions.push(Reaction {
+                emoji: emoji.to_string(),
+                users: vec![user],
+            }),
+        }
+    }
+
+    /// Take the `emoji` of `user` off this message, an emoji nobody put there anymore is gone
+    pub fn remove_reaction(&mut self, emoji: &str, user: &UsrID) {
+        for reaction in self.reactions.iter_mut().filter(|r| r.emoji == emoji) {
+            reaction.users.retain(|u| u != user);
+        }
+        self.reactions.retain(|r| !r.users.is_empty());
+    }
+
+    /// Replace what the message says, the old content goes into the history
+    pub fn edit(&mut self, content: String) {
+        let old = std::mem::replace(&mut self.content, content);
+        self.history.push(old);
+        self.edited_at = Some(Utc::now());
+    }
+
+    /// Turn the message into a tombstone, what it said and the reactions to it are dropped
+    pub fn delete(&mut self) {
+        self.deleted = true;
+        self.content.clear();
+        self.history.clear();
+        self.reactions.clear();
+    }
+
+    /// The session this message belongs to, seen from the side of user `me`
+    pub fn session_for(&self, me: &UsrID) -> ChatSession {
+        match self.to {
+            Receiver::Group(ref grp) => ChatSession::Group(grp.clone()),
+            Receiver::Individual(ref usr) if self.from == *me => {
+                ChatSession::WithOther(usr.clone())
+            }
+            Receiver::Individual(_) => ChatSession::WithOther(self.from.clone()),
+        }
+    }
+}
+
+/// What to look for in the messages of a session
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchQuery {
+    pub text: String,
+    // the text is a regular expression, not plain text
+    #[serde(default)]
+    pub regex: bool,
+    #[serde(default)]
+    pub case_sensitive: bool,
+}
+
+impl SearchQuery {
+    /// The query compiled, plain text is matched as it is
+    pub fn matcher(&self) -> Result<Regex, String> {
+        let pattern = match self.regex {
+            true => self.text.clone(),
+            false => fancy_regex::escape(&self.text).into_owned(),
+        };
+        RegexBuilder::new(&pattern)
+            .case_insensitive(!self.case_sensitive)
+            .build()
+            .map_err(|err| err.to_string())
+    }
+
+    /// Whether the message says what `matcher` looks for, a deleted one says nothing
+    pub fn found_in(matcher: &Regex, message: &Message) -> bool {
+        !message.deleted && matcher.is_match(&message.content).unwrap_or(false)
+    }
 }
 
 pub struct UserPool {
@@ -89,24 +260,40 @@ pub struct MessagePool {
     messages: Vec<Message>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum ChatSession {
     WithOther(UsrID),
     Group(GrpID),
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SessionRecord {
     pub session: ChatSession,
     pub bookmark: MsgID,
     pub unread_msg: usize,
+    #[serde(default)]
+    pub last_message: Option<Message>,
 }
 
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SessionPool(pub Vec<SessionRecord>);
 
+/// Data living on the backend, as far as the app knows about it.
+///
+/// Data already on screen is never thrown away by a refresh:
+/// `Refreshing` and `Failed` keep it around until the backend answers.
+#[derive(Default)]
 pub enum RemoteData<T> {
     Success(T),
-    Failed,
+    // showing the last known data, a fetch is on its way
+    Refreshing(T),
+    Failed {
+        last_good: Option<T>,
+        error: String,
+    },
     Pending,
+    #[default]
     Uninitialized,
 }
 
@@ -114,9 +301,96 @@ impl<T> RemoteData<T> {
     pub fn as_ref(&self) -> RemoteData<&T> {
         match *self {
             Self::Success(ref x) => RemoteData::Success(x),
-            Self::Failed => RemoteData::Failed,
+            Self::Refreshing(ref x) => RemoteData::Refreshing(x),
+            Self::Failed {
+                ref last_good,
+                ref error,
+            } => RemoteData::Failed {
+                last_good: last_good.as_ref(),
+                error: error.clone(),
+            },
             Self::Pending => RemoteData::Pending,
             Self::Uninitialized => RemoteData::Uninitialized,
         }
     }
+
+    /// The data to show, which may be stale
+    pub fn data(&self) -> Option<&T> {
+        match self {
+            Self::Success(x) | Self::Refreshing(x) => Some(x),
+            Self::Failed { last_good, .. } => last_good.as_ref(),
+            Self::Pending | Self::Uninitialized => None,
+        }
+    }
+
+    pub fn data_mut(&mut self) -> Option<&mut T> {
+        match self {
+            Self::Success(x) | Self::Refreshing(x) => Some(x),
+            Self::Failed { last_good, .. } => last_good.as_mut(),
+            Self::Pending | Self::Uninitialized => None,
+        }
+    }
+
+    pub fn into_data(self) -> Option<T> {
+        match self {
+            Self::Success(x) | Self::Refreshing(x) => Some(x),
+            Self::Failed { last_good, .. } => last_good,
+            Self::Pending | Self::Uninitialized => None,
+        }
+    }
+
+    pub fn is_loading(&self) -> bool {
+        matches!(self, Self::Refreshing(_) | Self::Pending)
+    }
+
+    pub fn error(&self) -> Option<&str> {
+        match self {
+            Self::Failed { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+
+    /// Start a fetch, keeping whatever data there is on screen
+    pub fn into_refreshing(self) -> Self {
+        match self {
+            Self::Success(x) | Self::Refreshing(x) => Self::Refreshing(x),
+            Self::Failed {
+                last_good: Some(x), ..
+            } => Self::Refreshing(x),
+            _ => Self::Pending,
+        }
+    }
+
+    /// A fetch failed, keeping whatever data there is on screen
+    pub fn into_failed(self, error: String) -> Self {
+        match self {
+            Self::Success(x) | Self::Refreshing(x) => Self::Failed {
+                last_good: Some(x),
+                error,
+            },
+            Self::Failed { last_good, .. } => Self::Failed { last_good, error },
+            _ => Self::Failed {
+                last_good: None,
+                error,
+            },
+        }
+    }
+}
+
+/// Messages the tests of the models and the ui are written with
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use super::{DeliveryStatus, Message, MsgID, Receiver, UsrID};
+
+    // a message of `from` to kevin, sent just now and taken by the backend
+    pub(crate) fn message(id: &str, from: &str, content: &str) -> Message {
+        let mut message = Message::outgoing(
+            MsgID(id.to_string()),
+            UsrID(from.to_string()),
+            Receiver::Individual(UsrID("kevin".to_string())),
+            content.to_string(),
+        );
+        message.status = DeliveryStatus::Sent;
+        message
+    }
 }
@@ -0,0 +1,60 @@
+/// Retry decides when a failed fetch is tried again.
+///
+/// The delay doubles with every failed attempt, starting at `BASE_DELAY` and capped at `MAX_DELAY`.
+/// After `MAX_ATTEMPTS` the models give up and wait for the user to press `r`.
+use tokio::sync::mpsc::UnboundedSender;
+
+use std::time::Duration;
+
+use crate::action::Action;
+
+const BASE_DELAY: Duration = Duration::from_secs(1);
+const MAX_DELAY: Duration = Duration::from_secs(30);
+const MAX_ATTEMPTS: u32 = 6;
+
+#[derive(Default)]
+pub struct Backoff {
+    attempt: u32,
+}
+
+impl Backoff {
+    /// How long to wait before the next attempt, none if it is time to give up
+    pub fn next_delay(&mut self) -> Option<Duration> {
+        if self.attempt >= MAX_ATTEMPTS {
+            return None;
+        }
+        let delay = BASE_DELAY.saturating_mul(1 << self.attempt).min(MAX_DELAY);
+        self.attempt += 1;
+        Some(delay)
+    }
+
+    pub fn reset(&mut self) {
+        self.attempt = 0;
+    }
+}
+
+/// Send `action` into the action channel once `delay` has passed
+pub fn send_after(action_tx: UnboundedSender<Action>, delay: Duration, action: Action) {
+    tokio::spawn(async move {
+        tokio::time::sleep(delay).await;
+        // the app is gone if this fails, nothing left to retry
+        let _ = action_tx.send(action);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_and_gives_up() {
+        let mut backoff = Backoff::default();
+        let delays = std::iter::from_fn(|| backoff.next_delay())
+            .map(|d| d.as_secs())
+            .collect::<Vec<_>>();
+        assert_eq!(delays, vec![1, 2, 4, 8, 16, 30]);
+
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), Some(BASE_DELAY));
+    }
+}
@@ -1,34 +1,58 @@
 use tokio::sync::mpsc::UnboundedSender;
 
-use std::time::Duration;
-
 use crate::action::{Action, SessionsModelAction};
+use crate::backend::SharedBackend;
+
+use std::collections::HashMap;
 
-use super::{ChatSession, GrpID, MsgID, RemoteData, SessionPool, SessionRecord, UsrID};
+use super::retry::{self, Backoff};
+use super::store::SharedStore;
+use super::{ChatSession, Message, RemoteData, SessionPool, SessionRecord, UserState, UsrID};
 
 pub struct SessionsModel {
     sessions: RemoteData<SessionPool>,
+    presence: HashMap<UsrID, UserState>,
     action_tx: UnboundedSender<Action>,
+    backend: SharedBackend,
+    store: SharedStore,
+    backoff: Backoff,
 }
 
 impl SessionsModel {
-    pub fn new(action_tx: UnboundedSender<Action>) -> Self {
-        Self {
-            sessions: RemoteData::Uninitialized,
+    pub fn new(
+        action_tx: UnboundedSender<Action>,
+        backend: SharedBackend,
+        store: SharedStore,
+    ) -> Self {
+        // start on the sessions we have on disk, the fetch reconciles them with the backend
+        let sessions = match store.borrow().sessions() {
+            Some(pool) => RemoteData::Success(pool.clone()),
+            None => RemoteData::Uninitialized,
+        };
+        let mut ret = Self {
+            sessions,
+            presence: HashMap::new(),
             action_tx,
-        }
+            backend,
+            store,
+            backoff: Backoff::default(),
+        };
+        ret.revalidate();
+        ret
     }
 
     pub fn get_model_data(&self) -> RemoteData<&SessionPool> {
-        match &self.sessions {
-            RemoteData::Uninitialized => {
-                self.action_tx
-                    .send(Action::SessionsModel(SessionsModelAction::Fetch))
-                    .unwrap();
-                RemoteData::Pending
-            }
-            _ => self.sessions.as_ref(),
-        }
+        self.sessions.as_ref()
+    }
+
+    pub fn get_record(&self, session: &ChatSession) -> Option<&SessionRecord> {
+        self.sessions
+            .data()
+            .and_then(|pool| pool.0.iter().find(|r| r.session == *session))
+    }
+
+    pub fn get_presence(&self, user: &UsrID) -> Option<&UserState> {
+        self.presence.get(user)
     }
 
     pub fn handle_action(&mut self, action: SessionsModelAction) {
@@ -45,56 +69,126 @@ impl SessionsModel {
             SessionsModelAction::Set(v) => {
                 self.act_on_set(v);
             }
+            SessionsModelAction::FetchFailed(error) => {
+                self.act_on_fetch_failed(error);
+            }
+            SessionsModelAction::Retry => {
+                if self.sessions.error().is_some() {
+                    self.revalidate();
+                }
+            }
+            SessionsModelAction::MarkRead(session) => {
+                self.act_on_mark_read(session);
+            }
+            SessionsModelAction::Update(record) => {
+                self.act_on_update(record);
+            }
+            SessionsModelAction::SetPresence(user, state) => {
+                self.presence.insert(user, state);
+            }
+            SessionsModelAction::NewMessage {
+                session,
+                message,
+                unread,
+            } => {
+                self.act_on_new_message(session, message, unread);
+            }
         }
     }
 
-    fn fetch() -> SessionPool {
-        // Pseudo implementation: init with some fake data
-        let sessions = vec![
-            SessionRecord {
-                session: ChatSession::WithOther(UsrID(String::from("Alice"))),
-                bookmark: MsgID(String::from("0")),
-                unread_msg: 0,
-            },
-            SessionRecord {
-                session: ChatSession::WithOther(UsrID(String::from("Bob"))),
-                bookmark: MsgID(String::from("0")),
-                unread_msg: 1,
-            },
-            SessionRecord {
-                session: ChatSession::Group(GrpID(String::from("Nordic Nostalgia"))),
-                bookmark: MsgID(String::from("0")),
-                unread_msg: 5,
-            },
-        ];
-
-        SessionPool(sessions)
+    // the sessions on screen stay there until the fetch has an answer
+    fn revalidate(&mut self) {
+        self.sessions = std::mem::take(&mut self.sessions).into_refreshing();
+        // the receiver only goes away when the app quits
+        let _ = self
+            .action_tx
+            .send(Action::SessionsModel(SessionsModelAction::Fetch));
     }
 
-    // TODO: should the data set to empty pending or use the cached data to display?
-    // Better statemachine on RemoteData
+    // asked by the user, so start over with the retries as well
     fn act_on_reload(&mut self) {
-        // TODO: maybe reuse the garbage here, not drop vec entirely, keep the space
-        // currently I just keep things simple
-        self.sessions = RemoteData::Pending;
-        self.action_tx
-            .send(Action::SessionsModel(SessionsModelAction::Fetch))
-            .unwrap();
+        self.backoff.reset();
+        self.revalidate();
     }
 
     fn act_on_fetch(&mut self) {
         let _tx = self.action_tx.clone();
+        let backend = self.backend.clone();
 
         tokio::spawn(async move {
-            // TODO: fetch will be a async funtion, currently we use sleep to simulate
-            tokio::time::sleep(Duration::from_secs(3)).await;
-            let data = SessionsModel::fetch();
-            _tx.send(Action::SessionsModel(SessionsModelAction::Set(data)))
-                .unwrap();
+            let action = match backend.list_sessions().await {
+                Ok(data) => SessionsModelAction::Set(data),
+                Err(err) => SessionsModelAction::FetchFailed(err.to_string()),
+            };
+            let _ = _tx.send(Action::SessionsModel(action));
         });
     }
 
     fn act_on_set(&mut self, data: SessionPool) {
+        self.store.borrow_mut().put_sessions(data.clone());
+        self.backoff.reset();
         self.sessions = RemoteData::Success(data);
     }
+
+    fn act_on_fetch_failed(&mut self, error: String) {
+        self.sessions = std::mem::take(&mut self.sessions).into_failed(error);
+        if let Some(delay) = self.backoff.next_delay() {
+            let action = Action::SessionsModel(SessionsModelAction::Retry);
+            retry::send_after(self.action_tx.clone(), delay, action);
+        }
+    }
+
+    // unread counters and previews change with every event, keep the store in sync
+    fn write_through(&self) {
+        if let Some(pool) = self.sessions.data() {
+            self.store.borrow_mut().put_sessions(pool.clone());
+        }
+    }
+
+    fn act_on_mark_read(&mut self, session: ChatSession) {
+        if let Some(pool) = self.sessions.data_mut() {
+            if let Some(record) = pool.0.iter_mut().find(|r| r.session == session) {
+                record.unread_msg = 0;
+            }
+        }
+        self.write_through();
+    }
+
+    fn act_on_update(&mut self, record: SessionRecord) {
+        let Some(pool) = self.sessions.data_mut() else {
+            // the full list is on its way, it will contain this record as well
+            return;
+        };
+        match pool.0.iter_mut().find(|r| r.session == record.session) {
+            Some(old) => *old = record,
+            None => pool.0.push(record),
+        }
+        self.write_through();
+    }
+
+    fn act_on_new_message(&mut self, session: ChatSession, message: Message, unread: bool) {
+        let Some(pool) = self.sessions.data_mut() else {
+            return;
+        };
+        let idx = match pool.0.iter().position(|r| r.session == session) {
+            Some(idx) => idx,
+            None => {
+                pool.0.push(SessionRecord {
+                    session,
+                    bookmark: message.id.clone(),
+                    unread_msg: 0,
+                    last_message: None,
+                });
+                pool.0.len() - 1
+            }
+        };
+
+        let record = &mut pool.0[idx];
+        if unread {
+            record.unread_msg += 1;
+        }
+        record.bookmark = message.id.clone();
+        record.last_message = Some(message);
+        self.write_through();
+    }
 }
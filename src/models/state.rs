@@ -5,6 +5,10 @@ pub enum StateModel {
     #[default]
     Chats,
     Messages,
+    Composer,
+    // the thread open next to the conversation, and the composer under it
+    Thread,
+    ThreadComposer,
     FPS,
 }
 
@@ -23,7 +27,10 @@ impl StateModel {
     fn act_on_next(&mut self) {
         match self {
             Self::Chats => *self = Self::Messages,
-            Self::Messages => *self = Self::FPS,
+            Self::Messages => *self = Self::Composer,
+            Self::Composer => *self = Self::FPS,
+            Self::Thread => *self = Self::ThreadComposer,
+            Self::ThreadComposer => *self = Self::Messages,
             Self::FPS => *self = Self::Chats,
         }
     }
@@ -0,0 +1,287 @@
+/// Store keeps a copy of everything the backend sent us on disk,
+/// so the app can start instantly on cached history and reconcile with the backend afterwards.
+///
+/// The file is an append-only log of JSON lines under the XDG data dir,
+/// e.g. `~/.local/share/chat-tui/<account>.jsonl`:
+/// - every write appends one `StoreRecord`, later records win over earlier ones.
+/// - on open the log is replayed and compacted into one record per session,
+///   a half-written last line (e.g. after a crash) is skipped.
+use color_eyre::eyre::{eyre, Result};
+use serde::{Deserialize, Serialize};
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+use super::{ChatSession, Message, SessionPool};
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "record", rename_all = "snake_case")]
+enum StoreRecord {
+    Sessions {
+        pool: SessionPool,
+    },
+    Messages {
+        session: ChatSession,
+        messages: Vec<Message>,
+    },
+    // boxed, a message is much bigger than the other records
+    Message {
+        session: ChatSession,
+        message: Box<Message>,
+    },
+    // a page of history older than what is stored
+    Older {
+        session: ChatSession,
+        messages: Vec<Message>,
+    },
+}
+
+#[derive(Default)]
+pub struct LocalStore {
+    // none means nothing is written to disk
+    log: Option<File>,
+    // why writing to disk stopped, if it did
+    write_error: Option<String>,
+    sessions: Option<SessionPool>,
+    messages: HashMap<ChatSession, Vec<Message>>,
+}
+
+pub type SharedStore = Rc<RefCell<LocalStore>>;
+
+impl LocalStore {
+    /// A store that lives only as long as the app
+    pub fn in_memory() -> Self {
+        Default::default()
+    }
+
+    /// Open the store of `account` under the XDG data dir
+    pub fn open(account: &str) -> Result<Self> {
+        let dir = dirs::data_dir()
+            .ok_or_else(|| eyre!("no data directory found"))?
+            .join("chat-tui");
+        std::fs::create_dir_all(&dir)?;
+        Self::open_at(&dir.join(format!("{}.jsonl", sanitize(account))))
+    }
+
+    pub fn open_at(path: &Path) -> Result<Self> {
+        let mut store = Self::in_memory();
+        if path.exists() {
+            for line in BufReader::new(File::open(path)?).lines() {
+                // skip what can not be read instead of refusing to start
+                if let Ok(record) = serde_json::from_str::<StoreRecord>(&line?) {
+                    store.apply(record);
+                }
+            }
+        }
+        store.compact(path)?;
+        Ok(store)
+    }
+
+    pub fn sessions(&self) -> Option<&SessionPool> {
+        self.sessions.as_ref()
+    }
+
+    pub fn messages(&self, session: &ChatSession) -> Option<&Vec<Message>> {
+        self.messages.get(session)
+    }
+
+    /// Why the store stopped writing to disk, what is put since lives only as long as the app
+    pub fn write_error(&self) -> Option<&str> {
+        self.write_error.as_deref()
+    }
+
+    pub fn put_sessions(&mut self, pool: SessionPool) {
+        self.write(StoreRecord::Sessions { pool });
+    }
+
+    pub fn put_messages(&mut self, session: ChatSession, messages: Vec<Message>) {
+        self.write(StoreRecord::Messages { session, messages });
+    }
+
+    /// Insert a new message, or replace the stored one with the same id,
+    /// it is dropped if the history of `session` is not in the store
+    pub fn put_message(&mut self, session: ChatSession, message: Message) {
+        let message = Box::new(message);
+        self.write(StoreRecord::Message { session, message });
+    }
+
+    /// Put a page of older messages before the stored history of `session`,
+    /// the ones already stored are skipped, the page is dropped if there is no history to extend
+    pub fn put_older_messages(&mut self, session: ChatSession, messages: Vec<Message>) {
+        self.write(StoreRecord::Older { session, messages });
+    }
+
+    fn apply(&mut self, record: StoreRecord) {
+        match record {
+            StoreRecord::Sessions { pool } => self.sessions = Some(pool),
+            StoreRecord::Messages { session, messages } => {
+                self.messages.insert(session, messages);
+            }
+            StoreRecord::Message { session, message } => {
+                // a session never fetched has no history to add to,
+                // caching this message alone would look like the complete history
+                let Some(messages) = self.messages.get_mut(&session) else {
+                    return;
+                };
+                match messages.iter_mut().find(|m| m.id == message.id) {
+                    Some(old) => *old = *message,
+                    None => messages.push(*message),
+                }
+            }
+            StoreRecord::Older {
+                session,
+                messages: older,
+            } => {
+                let Some(messages) = self.messages.get_mut(&session) else {
+                    return;
+                };
+                let older = older
+                    .into_iter()
+                    .filter(|m| messages.iter().all(|old| old.id != m.id))
+                    .collect::<Vec<_>>();
+                messages.splice(0..0, older);
+            }
+        }
+    }
+
+    fn write(&mut self, record: StoreRecord) {
+        if let Some(ref mut log) = self.log {
+            let written = serde_json::to_string(&record)
+                .map_err(std::io::Error::from)
+                .and_then(|line| writeln!(log, "{}", line));
+            // a log that failed once may be cut in the middle of a line, nothing more is written to it
+            if let Err(err) = written {
+                self.log = None;
+                self.write_error = Some(err.to_string());
+            }
+        }
+        self.apply(record);
+    }
+
+    // rewrite the log with one record per session, then keep appending to it
+    fn compact(&mut self, path: &Path) -> Result<()> {
+        let tmp_path = path.with_extension("jsonl.tmp");
+        let mut tmp = File::create(&tmp_path)?;
+        if let Some(ref pool) = self.sessions {
+            let record = StoreRecord::Sessions { pool: pool.clone() };
+            writeln!(tmp, "{}", serde_json::to_string(&record)?)?;
+        }
+        for (session, messages) in self.messages.iter() {
+            let record = StoreRecord::Messages {
+                session: session.clone(),
+                messages: messages.clone(),
+            };
+            writeln!(tmp, "{}", serde_json::to_string(&record)?)?;
+        }
+        tmp.sync_all()?;
+        std::fs::rename(&tmp_path, path)?;
+
+        self.log = Some(OpenOptions::new().append(true).open(path)?);
+        Ok(())
+    }
+}
+
+// account names come from user input, keep them to something safe for a file name
+fn sanitize(account: &str) -> String {
+    account
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{fixtures::message, MsgID, SessionRecord, UsrID};
+
+    #[test]
+    fn test_reopen_restores_written_data() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("alice.jsonl");
+        let bob = ChatSession::WithOther(UsrID("bob".to_string()));
+
+        let mut store = LocalStore::open_at(&path).unwrap();
+        store.put_sessions(SessionPool(vec![SessionRecord {
+            session: bob.clone(),
+            bookmark: MsgID("1".to_string()),
+            unread_msg: 2,
+            last_message: None,
+        }]));
+        store.put_messages(
+            bob.clone(),
+            vec![message("1", "alice", "hi"), message("2", "alice", "yo")],
+        );
+        store.put_message(bob.clone(), message("2", "alice", "yo!"));
+        store.put_message(bob.clone(), message("3", "alice", "bye"));
+        drop(store);
+
+        let store = LocalStore::open_at(&path).unwrap();
+        assert_eq!(store.sessions().unwrap().0[0].unread_msg, 2);
+        let contents = store
+            .messages(&bob)
+            .unwrap()
+            .iter()
+            .map(|m| m.content.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(contents, vec!["hi", "yo!", "bye"]);
+
+        // compacted into one record per session
+        let lines = std::fs::read_to_string(&path).unwrap().lines().count();
+        assert_eq!(lines, 2);
+    }
+
+    #[test]
+    fn test_older_pages_are_appended_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("alice.jsonl");
+        let bob = ChatSession::WithOther(UsrID("bob".to_string()));
+
+        let mut store = LocalStore::open_at(&path).unwrap();
+        store.put_messages(
+            bob.clone(),
+            vec![message("3", "alice", "c"), message("4", "alice", "d")],
+        );
+        store.put_older_messages(
+            bob.clone(),
+            vec![message("2", "alice", "b"), message("3", "alice", "c")],
+        );
+        store.put_older_messages(bob.clone(), vec![message("1", "alice", "a")]);
+        let log = std::fs::read_to_string(&path).unwrap();
+        let last = log.lines().last().unwrap();
+        assert!(!last.contains("\"b\"") && last.contains("\"a\""));
+        drop(store);
+
+        let store = LocalStore::open_at(&path).unwrap();
+        let ids = store
+            .messages(&bob)
+            .unwrap()
+            .iter()
+            .map(|m| m.id.0.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, vec!["1", "2", "3", "4"]);
+    }
+
+    #[test]
+    fn test_half_written_line_is_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("alice.jsonl");
+        let bob = ChatSession::WithOther(UsrID("bob".to_string()));
+
+        let mut store = LocalStore::open_at(&path).unwrap();
+        store.put_messages(bob.clone(), vec![message("1", "alice", "hi")]);
+        drop(store);
+        let mut log = OpenOptions::new().append(true).open(&path).unwrap();
+        write!(log, "{{\"record\":\"message\",\"sess").unwrap();
+        drop(log);
+
+        let store = LocalStore::open_at(&path).unwrap();
+        assert_eq!(store.messages(&bob).unwrap().len(), 1);
+    }
+}
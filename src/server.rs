@@ -0,0 +1,1011 @@
+/// Server is a small self-hostable chat server for development and demos.
+/// It keeps everything in memory and speaks the protocol described in `backend::protocol`.
+///
+/// - Everyone who ever logged in shows up as a direct session for everyone else.
+/// - Every user is a member of every group, a `Nordic Nostalgia` group exists from the start.
+use color_eyre::eyre::Result;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::{self, UnboundedSender};
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+
+use crate::backend::protocol::{self, Event, Request, RequestFrame, Response, ServerFrame};
+use crate::models::{
+    ChatSession, DeliveryStatus, GrpID, Message, MsgID, SearchQuery, SessionRecord, UserState,
+    UsrID,
+};
+
+pub const DEFAULT_ADDR: &str = "127.0.0.1:7878";
+const SEED_GROUP: &str = "Nordic Nostalgia";
+
+type ConnID = u64;
+
+// a conversation is the same no matter which side looks at it
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Conversation {
+    Direct(UsrID, UsrID),
+    Group(GrpID),
+}
+
+impl Conversation {
+    fn of(me: &UsrID, session: &ChatSession) -> Self {
+        match session {
+            ChatSession::Group(grp) => Self::Group(grp.clone()),
+            ChatSession::WithOther(other) if other.0 < me.0 => {
+                Self::Direct(other.clone(), me.clone())
+            }
+            ChatSession::WithOther(other) => Self::Direct(me.clone(), other.clone()),
+        }
+    }
+}
+
+struct Client {
+    // none until the connection has logged in
+    user: Option<UsrID>,
+    tx: UnboundedSender<ServerFrame>,
+}
+
+struct ServerState {
+    users: BTreeSet<UsrID>,
+    groups: BTreeSet<GrpID>,
+    conversations: HashMap<Conversation, Vec<Message>>,
+    unread: HashMap<(UsrID, Conversation), usize>,
+    clients: HashMap<ConnID, Client>,
+    conn_counter: ConnID,
+    msg_counter: u64,
+}
+
+impl ServerState {
+    fn new() -> Self {
+        Self {
+            users: BTreeSet::new(),
+            groups: BTreeSet::from([GrpID(SEED_GROUP.to_string())]),
+            conversations: HashMap::new(),
+            unread: HashMap::new(),
+            clients: HashMap::new(),
+            conn_counter: 0,
+            msg_counter: 0,
+        }
+    }
+
+    fn members(&self, conversation: &Conversation) -> Vec<UsrID> {
+        match conversation {
+            Conversation::Direct(a, b) => vec![a.clone(), b.clone()],
+            Conversation::Group(_) => self.users.iter().cloned().collect(),
+        }
+    }
+
+    fn handle(&mut self, conn: ConnID, request: Request) -> Response {
+        let user = self.clients.get(&conn).and_then(|c| c.user.clone());
+        match (request, user) {
+            (Request::Login { user }, _) => self.login(conn, user),
+            (_, None) => Response::Error {
+                reason: String::from("login first"),
+            },
+            (Request::ListSessions, Some(me)) => self.list_sessions(&me),
+            (
+                Request::History {
+                    session,
+                    before,
+                    after,
+                    limit,
+                },
+                Some(me),
+            ) => self.history(&me, &session, before, after, limit),
+            (
+                Request::Send {
+                    session,
+                    content,
+                    reply_to,
+                    thread,
+                },
+                Some(me),
+            ) => self.send(conn, &me, session, content, reply_to, thread),
+            (
+                Request::AddReaction {
+                    session,
+                    message,
+                    emoji,
+                },
+                Some(me),
+            ) => self.react(conn, &me, session, message, emoji, true),
+            (
+                Request::RemoveReaction {
+                    session,
+                    message,
+                    emoji,
+                },
+                Some(me),
+            ) => self.react(conn, &me, session, message, emoji, false),
+            (
+                Request::Edit {
+                    session,
+                    message,
+                    content,
+                },
+                Some(me),
+            ) => self.edit(conn, &me, session, message, content),
+            (Request::Delete { session, message }, Some(me)) => {
+                self.delete(conn, &me, session, message)
+            }
+            (Request::Search { session, query }, Some(me)) => self.search(&me, &session, &query),
+        }
+    }
+
+    fn login(&mut self, conn: ConnID, user: UsrID) -> Response {
+        if user.0.trim().is_empty() {
+            return Response::Error {
+                reason: String::from("user name must not be empty"),
+            };
+        }
+        let was_online = self.is_online(&user);
+        let is_new = self.users.insert(user.clone());
+        if let Some(client) = self.clients.get_mut(&conn) {
+            client.user = Some(user.clone());
+        }
+
+        // everybody else learns about the newcomer
+        for client in self.clients.values() {
+            let Some(ref other) = client.user else {
+                continue;
+            };
+            if *other == user {
+                continue;
+            }
+            if is_new {
+                let record = self.session_record(other, ChatSession::WithOther(user.clone()));
+                let event = Event::SessionUpdated { record };
+                let _ = client.tx.send(ServerFrame::Event { event });
+            }
+            if !was_online {
+                let event = Event::PresenceChanged {
+                    user: user.clone(),
+                    state: UserState::Online,
+                };
+                let _ = client.tx.send(ServerFrame::Event { event });
+            }
+        }
+
+        // and the newcomer learns who is around
+        if let Some(client) = self.clients.get(&conn) {
+            let online = self
+                .users
+                .iter()
+                .filter(|usr| **usr != user && self.is_online(usr));
+            for other in online {
+                let event = Event::PresenceChanged {
+                    user: other.clone(),
+                    state: UserState::Online,
+                };
+                let _ = client.tx.send(ServerFrame::Event { event });
+            }
+        }
+
+        Response::Welcome { user }
+    }
+
+    fn logout(&mut self, conn: ConnID) {
+        let Some(Client {
+            user: Some(user), ..
+        }) = self.clients.remove(&conn)
+        else {
+            return;
+        };
+        if self.is_online(&user) {
+            return;
+        }
+        for client in self.clients.values().filter(|c| c.user.is_some()) {
+            let event = Event::PresenceChanged {
+                user: user.clone(),
+                state: UserState::Offline,
+            };
+            let _ = client.tx.send(ServerFrame::Event { event });
+        }
+    }
+
+    fn is_online(&self, user: &UsrID) -> bool {
+        self.clients
+            .values()
+            .any(|client| client.user.as_ref() == Some(user))
+    }
+
+    fn session_record(&self, me: &UsrID, session: ChatSession) -> SessionRecord {
+        let conversation = Conversation::of(me, &session);
+        let last_message = self
+            .conversations
+            .get(&conversation)
+            .and_then(|msgs| msgs.last())
+            .cloned();
+        let unread_msg = self
+            .unread
+            .get(&(me.clone(), conversation))
+            .copied()
+            .unwrap_or(0);
+        SessionRecord {
+            session,
+            bookmark: last_message
+                .as_ref()
+                .map_or(MsgID(String::from("0")), |msg| msg.id.clone()),
+            unread_msg,
+            last_message,
+        }
+    }
+
+    fn list_sessions(&self, me: &UsrID) -> Response {
+        let direct = self
+            .users
+            .iter()
+            .filter(|usr| *usr != me)
+            .map(|usr| ChatSession::WithOther(usr.clone()));
+        let groups = self
+            .groups
+            .iter()
+            .map(|grp| ChatSession::Group(grp.clone()));
+
+        let sessions = direct
+            .chain(groups)
+            .map(|session| self.session_record(me, session))
+            .collect();
+
+        Response::Sessions { sessions }
+    }
+
+    fn history(
+        &mut self,
+        me: &UsrID,
+        session: &ChatSession,
+        before: Option<MsgID>,
+        after: Option<MsgID>,
+        limit: Option<usize>,
+    ) -> Response {
+        let conversation = Conversation::of(me, session);
+        let messages = self
+            .conversations
+            .get(&conversation)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+
+        let end = match before {
+            Some(ref id) => messages
+                .iter()
+                .position(|msg| msg.id == *id)
+                .unwrap_or(messages.len()),
+            None => messages.len(),
+        };
+        // an unknown `after` gives the whole history, the client merges it by id
+        let after = match after {
+            Some(ref id) => messages
+                .iter()
+                .position(|msg| msg.id == *id)
+                .map_or(0, |idx| idx + 1),
+            None => 0,
+        };
+        let start = limit
+            .map_or(0, |limit| end.saturating_sub(limit))
+            .max(after)
+            .min(end);
+        let page = messages[start..end].to_vec();
+
+        // reading the latest messages means everything has been seen
+        if before.is_none() {
+            self.unread.remove(&(me.clone(), conversation));
+        }
+        Response::History { messages: page }
+    }
+
+    fn search(&self, me: &UsrID, session: &ChatSession, query: &SearchQuery) -> Response {
+        let matcher = match query.matcher() {
+            Ok(matcher) => matcher,
+            Err(reason) => return Response::Error { reason },
+        };
+        let messages = self
+            .conversations
+            .get(&Conversation::of(me, session))
+            .map(|messages| {
+                messages
+                    .iter()
+                    .filter(|msg| SearchQuery::found_in(&matcher, msg))
+                    .cloned()
+                    .collect()
+            })
+            .unwrap_or_default();
+        Response::History { messages }
+    }
+
+    fn send(
+        &mut self,
+        conn: ConnID,
+        me: &UsrID,
+        session: ChatSession,
+        content: String,
+        reply_to: Option<MsgID>,
+        thread: Option<MsgID>,
+    ) -> Response {
+        if content.trim().is_empty() {
+            return Response::Error {
+                reason: String::from("message must not be empty"),
+            };
+        }
+        // a conversation nobody would ever see in their sessions is not started
+        let unknown = match session {
+            ChatSession::WithOther(ref other) if other == me => {
+                Some(String::from("can not send a message to yourself"))
+            }
+            ChatSession::WithOther(ref other) if !self.users.contains(other) => {
+                Some(format!("no user {} to send to", other.0))
+            }
+            ChatSession::Group(ref grp) if !self.groups.contains(grp) => {
+                Some(format!("no group {} to send to", grp.0))
+            }
+            _ => None,
+        };
+        if let Some(reason) = unknown {
+            return Response::Error { reason };
+        }
+        let conversation = Conversation::of(me, &session);
+        let find = |id: &MsgID| {
+            self.conversations
+                .get(&conversation)
+                .and_then(|messages| messages.iter().find(|msg| msg.id == *id))
+        };
+        if let Some(ref parent) = reply_to {
+            if find(parent).is_none() {
+                return Response::Error {
+                    reason: format!("no message {} to reply to", parent.0),
+                };
+            }
+        }
+        // threads hang off a message of a group, and are not nested
+        if let Some(ref root) = thread {
+            let is_root = find(root).is_some_and(|msg| msg.thread.is_none());
+            if !matches!(session, ChatSession::Group(_)) || !is_root {
+                return Response::Error {
+                    reason: format!("no thread {} to post in", root.0),
+                };
+            }
+        }
+
+        self.msg_counter += 1;
+        let mut message = Message::outgoing(
+            MsgID(format!("m{}", self.msg_counter)),
+            me.clone(),
+            session.clone().into(),
+            content,
+        )
+        .with_reply_to(reply_to)
+        .with_thread(thread);
+        message.status = DeliveryStatus::Sent;
+
+        self.conversations
+            .entry(conversation.clone())
+            .or_default()
+            .push(message.clone());
+
+        let members = self.members(&conversation);
+        for member in members.iter().filter(|usr| *usr != me) {
+            *self
+                .unread
+                .entry((member.clone(), conversation.clone()))
+                .or_default() += 1;
+        }
+
+        // the sending connection learns about the message from the reply
+        for (id, client) in self.clients.iter() {
+            let Some(ref user) = client.user else {
+                continue;
+            };
+            if *id == conn || !members.contains(user) {
+                continue;
+            }
+            let event = Event::NewMessage {
+                session: message.session_for(user),
+                message: message.clone(),
+            };
+            let _ = client.tx.send(ServerFrame::Event { event });
+        }
+
+        Response::Sent { message }
+    }
+
+    fn react(
+        &mut self,
+        conn: ConnID,
+        me: &UsrID,
+        session: ChatSession,
+        id: MsgID,
+        emoji: String,
+        add: bool,
+    ) -> Response {
+        let emoji = emoji.trim();
+        if emoji.is_empty() {
+            return Response::Error {
+                reason: String::from("reaction must not be empty"),
+            };
+        }
+        let conversation = Conversation::of(me, &session);
+        let Some(message) = self
+            .conversations
+            .get_mut(&conversation)
+            .and_then(|messages| messages.iter_mut().find(|msg| msg.id == id))
+        else {
+            return Response::Error {
+                reason: format!("no message {} to react to", id.0),
+            };
+        };
+        // a deleted message had its reactions dropped, they are not put back
+        if message.deleted {
+            return Response::Error {
+                reason: format!("message {} is deleted", id.0),
+            };
+        }
+        if add {
+            message.add_reaction(emoji, me.clone());
+        } else {
+            message.remove_reaction(emoji, me);
+        }
+        let message = message.clone();
+        self.tell_updated(conn, &conversation, &message);
+        Response::Updated { message }
+    }
+
+    fn edit(
+        &mut self,
+        conn: ConnID,
+        me: &UsrID,
+        session: ChatSession,
+        id: MsgID,
+        content: String,
+    ) -> Response {
+        if content.trim().is_empty() {
+            return Response::Error {
+                reason: String::from("message must not be empty"),
+            };
+        }
+        let conversation = Conversation::of(me, &session);
+        let message = match self.own_message(me, &conversation, &id) {
+            Ok(message) if message.deleted => {
+                return Response::Error {
+                    reason: format!("message {} is deleted", id.0),
+                }
+            }
+            Ok(message) => message,
+            Err(reason) => return Response::Error { reason },
+        };
+        if message.content != content {
+            message.edit(content);
+        }
+        let message = message.clone();
+        self.tell_updated(conn, &conversation, &message);
+        Response::Updated { message }
+    }
+
+    fn delete(&mut self, conn: ConnID, me: &UsrID, session: ChatSession, id: MsgID) -> Response {
+        let conversation = Conversation::of(me, &session);
+        let message = match self.own_message(me, &conversation, &id) {
+            Ok(message) => message,
+            Err(reason) => return Response::Error { reason },
+        };
+        message.delete();
+        let message = message.clone();
+        self.tell_updated(conn, &conversation, &message);
+        Response::Updated { message }
+    }
+
+    // a message of the conversation that `me` has written, only the author may change it
+    fn own_message(
+        &mut self,
+        me: &UsrID,
+        conversation: &Conversation,
+        id: &MsgID,
+    ) -> std::result::Result<&mut Message, String> {
+        let message = self
+            .conversations
+            .get_mut(conversation)
+            .and_then(|messages| messages.iter_mut().find(|msg| msg.id == *id))
+            .ok_or_else(|| format!("no message {}", id.0))?;
+        if message.from != *me {
+            return Err(format!("message {} is not yours", id.0));
+        }
+        Ok(message)
+    }
+
+    // the connection that changed the message learns about it from the reply
+    fn tell_updated(&self, conn: ConnID, conversation: &Conversation, message: &Message) {
+        let members = self.members(conversation);
+        for (id, client) in self.clients.iter() {
+            let Some(ref user) = client.user else {
+                continue;
+            };
+            if *id == conn || !members.contains(user) {
+                continue;
+            }
+            let event = Event::MessageUpdated {
+                session: message.session_for(user),
+                message: message.clone(),
+            };
+            let _ = client.tx.send(ServerFrame::Event { event });
+        }
+    }
+}
+
+/// Bind to `addr` and serve clients until the process is killed
+pub async fn run(addr: &str) -> Result<()> {
+    let listener = TcpListener::bind(addr).await?;
+    serve(listener).await
+}
+
+pub async fn serve(listener: TcpListener) -> Result<()> {
+    let state = Arc::new(Mutex::new(ServerState::new()));
+    loop {
+        let (stream, _peer) = listener.accept().await?;
+        tokio::spawn(handle_connection(stream, state.clone()));
+    }
+}
+
+async fn handle_connection(stream: TcpStream, state: Arc<Mutex<ServerState>>) {
+    let (reader, mut writer) = stream.into_split();
+    let (tx, mut rx) = mpsc::unbounded_channel::<ServerFrame>();
+
+    let conn = {
+        let mut state = state.lock().unwrap();
+        state.conn_counter += 1;
+        let conn = state.conn_counter;
+        state.clients.insert(
+            conn,
+            Client {
+                user: None,
+                tx: tx.clone(),
+            },
+        );
+        conn
+    };
+
+    let writer_task = tokio::spawn(async move {
+        while let Some(frame) = rx.recv().await {
+            let Ok(line) = protocol::encode(&frame) else {
+                continue;
+            };
+            if writer.write_all(line.as_bytes()).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        let reply = match protocol::decode::<RequestFrame>(&line) {
+            Ok(RequestFrame { id, request }) => ServerFrame::Reply {
+                id,
+                response: state.lock().unwrap().handle(conn, request),
+            },
+            // without a readable id there is nobody to reply to
+            Err(_) => continue,
+        };
+        if tx.send(reply).is_err() {
+            break;
+        }
+    }
+
+    state.lock().unwrap().logout(conn);
+    drop(tx);
+    let _ = writer_task.await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::{tcp::TcpBackend, BackendEvent, ChatBackend};
+
+    async fn start_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+        tokio::spawn(serve(listener));
+        addr
+    }
+
+    fn usr(name: &str) -> UsrID {
+        UsrID(name.to_string())
+    }
+
+    // a server `users` are logged in to, the first one on connection 1, the next on 2 and so on
+    fn logged_in(users: &[&str]) -> ServerState {
+        let mut state = ServerState::new();
+        for (conn, user) in (1..).zip(users) {
+            state.users.insert(usr(user));
+            state.clients.insert(
+                conn,
+                Client {
+                    user: Some(usr(user)),
+                    tx: mpsc::unbounded_channel().0,
+                },
+            );
+        }
+        state
+    }
+
+    #[tokio::test]
+    async fn test_direct_message_round_trip() {
+        let addr = start_server().await;
+        let alice = TcpBackend::connect(&addr, usr("alice")).await.unwrap();
+        let bob = TcpBackend::connect(&addr, usr("bob")).await.unwrap();
+        let mut bob_events = bob.subscribe().await.unwrap();
+
+        let sessions = alice.list_sessions().await.unwrap();
+        assert_eq!(sessions.0.len(), 2);
+        assert!(sessions
+            .0
+            .iter()
+            .any(|r| r.session == ChatSession::WithOther(usr("bob"))));
+
+        let sent = alice
+            .send_message(
+                ChatSession::WithOther(usr("bob")),
+                "hi bob".to_string(),
+                None,
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(sent.from, usr("alice"));
+
+        let (session, pushed) = loop {
+            match bob_events.recv().await.unwrap() {
+                BackendEvent::NewMessage(session, pushed) => break (session, pushed),
+                BackendEvent::PresenceChanged(user, state) => {
+                    assert_eq!(user, usr("alice"));
+                    assert_eq!(state, UserState::Online);
+                }
+                BackendEvent::SessionUpdated(record) => {
+                    panic!("bob already knows alice: {:?}", record)
+                }
+                BackendEvent::MessageUpdated(_, message) => {
+                    panic!("nothing was changed: {:?}", message)
+                }
+            }
+        };
+        assert_eq!(session, ChatSession::WithOther(usr("alice")));
+        assert_eq!(pushed.id, sent.id);
+
+        let bob_sessions = bob.list_sessions().await.unwrap();
+        let record = bob_sessions
+            .0
+            .iter()
+            .find(|r| r.session == ChatSession::WithOther(usr("alice")))
+            .unwrap();
+        assert_eq!(record.unread_msg, 1);
+        assert_eq!(record.last_message.as_ref().unwrap().id, sent.id);
+
+        let history = bob
+            .fetch_messages(ChatSession::WithOther(usr("alice")))
+            .await
+            .unwrap();
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].content, "hi bob");
+
+        // a reply has to answer a message of the same conversation
+        let alice_session = ChatSession::WithOther(usr("alice"));
+        let reply = bob
+            .send_message(
+                alice_session.clone(),
+                "hi alice".to_string(),
+                Some(sent.id.clone()),
+                None,
+            )
+            .await
+            .unwrap();
+        assert_eq!(reply.reply_to, Some(sent.id));
+        assert!(bob
+            .send_message(
+                alice_session,
+                "huh".to_string(),
+                Some(MsgID("m0".to_string())),
+                None,
+            )
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_history_paging() {
+        let mut state = logged_in(&["alice"]);
+        let group = ChatSession::Group(GrpID(SEED_GROUP.to_string()));
+        for i in 0..10 {
+            state.handle(
+                1,
+                Request::Send {
+                    session: group.clone(),
+                    content: format!("message {}", i),
+                    reply_to: None,
+                    thread: None,
+                },
+            );
+        }
+
+        let mut page = |before: Option<&str>, limit| {
+            let before = before.map(|id| MsgID(id.to_string()));
+            match state.history(&usr("alice"), &group, before, None, limit) {
+                Response::History { messages } => {
+                    messages.into_iter().map(|msg| msg.id.0).collect::<Vec<_>>()
+                }
+                other => panic!("unexpected response {:?}", other),
+            }
+        };
+        assert_eq!(page(None, Some(3)), vec!["m8", "m9", "m10"]);
+        assert_eq!(page(Some("m8"), Some(3)), vec!["m5", "m6", "m7"]);
+        assert_eq!(page(Some("m2"), Some(3)), vec!["m1"]);
+        assert_eq!(page(None, None).len(), 10);
+
+        let mut since = |after: &str| match state.history(
+            &usr("alice"),
+            &group,
+            None,
+            Some(MsgID(after.to_string())),
+            None,
+        ) {
+            Response::History { messages } => {
+                messages.into_iter().map(|msg| msg.id.0).collect::<Vec<_>>()
+            }
+            other => panic!("unexpected response {:?}", other),
+        };
+        assert_eq!(since("m8"), vec!["m9", "m10"]);
+        assert_eq!(since("m10"), Vec::<String>::new());
+        assert_eq!(since("unknown").len(), 10);
+    }
+
+    // a server alice is logged in to, and what it answers to her sending "anyone?" to `session`
+    fn alice_sends(session: ChatSession) -> (ServerState, Result<(), String>) {
+        let mut state = logged_in(&["alice"]);
+        let request = Request::Send {
+            session,
+            content: String::from("anyone?"),
+            reply_to: None,
+            thread: None,
+        };
+        let sent = match state.handle(1, request) {
+            Response::Error { reason } => Err(reason),
+            _ => Ok(()),
+        };
+        (state, sent)
+    }
+
+    #[test]
+    fn test_send_to_unknown_user_is_refused() {
+        let (state, sent) = alice_sends(ChatSession::WithOther(usr("nobody")));
+        assert_eq!(sent, Err(String::from("no user nobody to send to")));
+        assert!(state.conversations.is_empty());
+    }
+
+    #[test]
+    fn test_send_to_yourself_is_refused() {
+        let (state, sent) = alice_sends(ChatSession::WithOther(usr("alice")));
+        assert_eq!(
+            sent,
+            Err(String::from("can not send a message to yourself"))
+        );
+        assert!(state.conversations.is_empty());
+    }
+
+    #[test]
+    fn test_send_to_unknown_group_is_refused() {
+        let (state, sent) = alice_sends(ChatSession::Group(GrpID(String::from("Hanseatic"))));
+        assert_eq!(sent, Err(String::from("no group Hanseatic to send to")));
+        assert!(state.conversations.is_empty());
+
+        let (state, sent) = alice_sends(ChatSession::Group(GrpID(SEED_GROUP.to_string())));
+        assert!(sent.is_ok());
+        assert_eq!(state.conversations.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_reactions_are_shared() {
+        let addr = start_server().await;
+        let alice = TcpBackend::connect(&addr, usr("alice")).await.unwrap();
+        let bob = TcpBackend::connect(&addr, usr("bob")).await.unwrap();
+        let mut bob_events = bob.subscribe().await.unwrap();
+        let group = ChatSession::Group(GrpID(SEED_GROUP.to_string()));
+
+        let sent = alice
+            .send_message(group.clone(), "lunch?".to_string(), None, None)
+            .await
+            .unwrap();
+        let liked = alice
+            .add_reaction(group.clone(), sent.id.clone(), "👍".to_string())
+            .await
+            .unwrap();
+        assert!(liked.reacted("👍", &usr("alice")));
+
+        let pushed = loop {
+            if let BackendEvent::MessageUpdated(session, message) = bob_events.recv().await.unwrap()
+            {
+                assert_eq!(session, group);
+                break message;
+            }
+        };
+        assert_eq!(pushed.reactions, liked.reactions);
+
+        // the same emoji twice is one reaction, the last one to take it off removes it
+        let liked = bob
+            .add_reaction(group.clone(), sent.id.clone(), "👍".to_string())
+            .await
+            .unwrap();
+        let liked = bob
+            .add_reaction(group.clone(), liked.id, "👍".to_string())
+            .await
+            .unwrap();
+        assert_eq!(liked.reactions[0].users, vec![usr("alice"), usr("bob")]);
+        alice
+            .remove_reaction(group.clone(), sent.id.clone(), "👍".to_string())
+            .await
+            .unwrap();
+        let unliked = bob
+            .remove_reaction(group.clone(), sent.id.clone(), "👍".to_string())
+            .await
+            .unwrap();
+        assert!(unliked.reactions.is_empty());
+
+        assert!(bob
+            .add_reaction(group, MsgID("m404".to_string()), "👍".to_string())
+            .await
+            .is_err());
+    }
+
+    #[test]
+    fn test_threads_hang_off_group_messages() {
+        let mut state = logged_in(&["alice", "bob"]);
+        let group = ChatSession::Group(GrpID(SEED_GROUP.to_string()));
+        let mut send = |session: &ChatSession, thread: Option<&str>| {
+            let request = Request::Send {
+                session: session.clone(),
+                content: String::from("hi"),
+                reply_to: None,
+                thread: thread.map(|id| MsgID(id.to_string())),
+            };
+            match state.handle(1, request) {
+                Response::Sent { message } => Ok(message.id.0),
+                Response::Error { reason } => Err(reason),
+                other => panic!("unexpected response {:?}", other),
+            }
+        };
+
+        let root = send(&group, None).unwrap();
+        let reply = send(&group, Some(&root)).unwrap();
+        assert!(send(&group, Some(&root)).is_ok());
+        // no threads in threads, nor outside of groups
+        assert!(send(&group, Some(&reply)).is_err());
+        assert!(send(&group, Some("m404")).is_err());
+        let bob = ChatSession::WithOther(usr("bob"));
+        let direct = send(&bob, None).unwrap();
+        assert!(send(&bob, Some(&direct)).is_err());
+    }
+
+    #[test]
+    fn test_only_authors_change_messages() {
+        let mut state = logged_in(&["alice", "bob"]);
+        let group = ChatSession::Group(GrpID(SEED_GROUP.to_string()));
+        let sent = match state.handle(
+            1,
+            Request::Send {
+                session: group.clone(),
+                content: String::from("lunch?"),
+                reply_to: None,
+                thread: None,
+            },
+        ) {
+            Response::Sent { message } => message.id,
+            other => panic!("unexpected response {:?}", other),
+        };
+        let mut change = |conn, request| match state.handle(conn, request) {
+            Response::Updated { message } => Ok(message),
+            Response::Error { reason } => Err(reason),
+            other => panic!("unexpected response {:?}", other),
+        };
+        let edit = |content: &str| Request::Edit {
+            session: group.clone(),
+            message: sent.clone(),
+            content: content.to_string(),
+        };
+        let delete = Request::Delete {
+            session: group.clone(),
+            message: sent.clone(),
+        };
+
+        assert!(change(2, edit("dinner?")).is_err());
+        assert!(change(2, delete.clone()).is_err());
+        let edited = change(1, edit("dinner?")).unwrap();
+        assert_eq!(edited.content, "dinner?");
+        assert_eq!(edited.history, vec!["lunch?"]);
+        assert!(edited.edited_at.is_some());
+
+        let deleted = change(1, delete).unwrap();
+        assert!(deleted.deleted);
+        assert!(deleted.content.is_empty() && deleted.history.is_empty());
+        assert!(change(1, edit("brunch?")).is_err());
+    }
+
+    #[test]
+    fn test_deleted_messages_take_no_reactions() {
+        let mut state = logged_in(&["alice"]);
+        let group = ChatSession::Group(GrpID(SEED_GROUP.to_string()));
+        let sent = match state.handle(
+            1,
+            Request::Send {
+                session: group.clone(),
+                content: String::from("lunch?"),
+                reply_to: None,
+                thread: None,
+            },
+        ) {
+            Response::Sent { message } => message.id,
+            other => panic!("unexpected response {:?}", other),
+        };
+        let react = Request::AddReaction {
+            session: group.clone(),
+            message: sent.clone(),
+            emoji: String::from("👍"),
+        };
+        assert!(matches!(
+            state.handle(1, react.clone()),
+            Response::Updated { .. }
+        ));
+        state.handle(
+            1,
+            Request::Delete {
+                session: group.clone(),
+                message: sent.clone(),
+            },
+        );
+
+        match state.handle(1, react) {
+            Response::Error { reason } => {
+                assert_eq!(reason, format!("message {} is deleted", sent.0))
+            }
+            other => panic!("unexpected response {:?}", other),
+        }
+        let conversation = Conversation::of(&usr("alice"), &group);
+        let message = state.conversations[&conversation]
+            .iter()
+            .find(|m| m.id == sent)
+            .unwrap();
+        assert!(message.reactions.is_empty());
+    }
+
+    #[test]
+    fn test_search_finds_what_messages_say() {
+        let mut state = logged_in(&["alice", "bob"]);
+        let bob = ChatSession::WithOther(usr("bob"));
+        for content in ["Lunch at noon?", "lunch is late", "see you (soon)"] {
+            let request = Request::Send {
+                session: bob.clone(),
+                content: content.to_string(),
+                reply_to: None,
+                thread: None,
+            };
+            state.handle(1, request);
+        }
+        let mut search = |text: &str, regex: bool, case_sensitive: bool| {
+            let query = SearchQuery {
+                text: text.to_string(),
+                regex,
+                case_sensitive,
+            };
+            let request = Request::Search {
+                session: bob.clone(),
+                query,
+            };
+            match state.handle(1, request) {
+                Response::History { messages } => Ok(messages.len()),
+                Response::Error { reason } => Err(reason),
+                other => panic!("unexpected response {:?}", other),
+            }
+        };
+
+        assert_eq!(search("lunch", false, false), Ok(2));
+        assert_eq!(search("lunch", false, true), Ok(1));
+        // plain text is taken as it is, a regex is not
+        assert_eq!(search("(soon)", false, false), Ok(1));
+        assert_eq!(search("^lunch|noon", true, false), Ok(2));
+        assert!(search("(unclosed", true, false).is_err());
+    }
+}
@@ -8,9 +8,12 @@
 ///     - User-defined events: such as Tick and Render, which is generated by a async timer.
 /// - Writing Output to Terminal, aka rendering:
 ///     - Here, we only used `ratatui::terminal::draw` to render UI in the terminal.
+///     - Besides the clipboard, which is reached through an escape sequence as well.
+use base64::Engine;
 use color_eyre::eyre::Result;
 use crossterm::cursor;
 use crossterm::event::{Event as RawEvent, KeyEvent, MouseEvent};
+use crossterm::style::Print;
 use crossterm::terminal::{EnterAlternateScreen, LeaveAlternateScreen};
 use futures::{future::FutureExt, StreamExt};
 use ratatui::backend::CrosstermBackend as Backend;
@@ -38,13 +41,12 @@ pub enum TerminalEvent {
 
 impl From<std::io::Result<RawEvent>> for TerminalEvent {
     fn from(raw_event: std::io::Result<RawEvent>) -> Self {
-        if let Err(_err) = raw_event {
+        let Ok(raw_event) = raw_event else {
             // TODO: print this unknow error to log file
             return TerminalEvent::Error;
-        }
+        };
 
-        // unwrap safe here, since we have already checked the error
-        match raw_event.unwrap() {
+        match raw_event {
             RawEvent::Key(keyev) if keyev.kind == crossterm::event::KeyEventKind::Press => {
                 TerminalEvent::Key(keyev)
             }
@@ -105,21 +107,20 @@ impl Tio {
                 let future_tick = tick_timer.tick();
                 let future_render = render_timer.tick();
                 let future_raw_event = raw_event_reader.next().fuse();
-                tokio::select! {
+                let event = tokio::select! {
                     _ = _cancel_token.cancelled() => {
                         break;
                     },
-                    raw_evt = future_raw_event => {
-                        if let Some(raw) = raw_evt {
-                            _evt_tx.send(TerminalEvent::from(raw)).unwrap();
-                        }
-                    },
-                    _ = future_tick => {
-                        _evt_tx.send(TerminalEvent::Tick).unwrap();
-                    },
-                    _ = future_render => {
-                        _evt_tx.send(TerminalEvent::Render).unwrap();
+                    raw_evt = future_raw_event => match raw_evt {
+                        Some(raw) => TerminalEvent::from(raw),
+                        None => continue,
                     },
+                    _ = future_tick => TerminalEvent::Tick,
+                    _ = future_render => TerminalEvent::Render,
+                };
+                // nobody is listening anymore, the app is on its way out
+                if _evt_tx.send(event).is_err() {
+                    break;
                 }
             }
         });
@@ -152,6 +153,11 @@ impl Tio {
 
     pub fn leave(&mut self) -> Result<()> {
         self.stop();
+        Self::restore()
+    }
+
+    /// Put the terminal back the way it was before `enter`
+    pub fn restore() -> Result<()> {
         if crossterm::terminal::is_raw_mode_enabled()? {
             crossterm::execute!(sys_io(), LeaveAlternateScreen, cursor::Show)?;
             crossterm::terminal::disable_raw_mode()?;
@@ -159,11 +165,36 @@ impl Tio {
         Ok(())
     }
 
+    /// Restore the terminal before a panic message is printed,
+    /// otherwise the message is lost in the alternate screen
+    pub fn install_panic_hook() {
+        let hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            let _ = Self::restore();
+            hook(info);
+        }));
+    }
+
+    /// Put `text` on the system clipboard with an OSC 52 sequence,
+    /// the terminal does the copying, so it works over ssh too, if the terminal allows it
+    pub fn copy_to_clipboard(text: &str) -> Result<()> {
+        let encoded = base64::engine::general_purpose::STANDARD.encode(text);
+        crossterm::execute!(sys_io(), Print(format!("\x1b]52;c;{}\x07", encoded)))?;
+        Ok(())
+    }
+
     pub async fn next_event(&mut self) -> Option<TerminalEvent> {
         self.event_rx.recv().await
     }
 }
 
+// an error returned halfway through the app must not leave the terminal in raw mode
+impl Drop for Tio {
+    fn drop(&mut self) {
+        let _ = self.leave();
+    }
+}
+
 mod tests {
 
     #[tokio::test]
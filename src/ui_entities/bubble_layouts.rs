@@ -0,0 +1,244 @@
+/// BubbleLayouts keeps the bubbles of the messages laid out, so a frame only paints them.
+///
+/// A bubble is laid out once per message and width, and again only when the message
+/// or what is drawn around it changes. The message viewer and the thread pane measure
+/// every bubble with it, then paint just the ones `in_sight`.
+use chrono::{Local, NaiveDate};
+use fancy_regex::Regex;
+use ratatui::prelude::*;
+use ratatui::text::Text;
+
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::rc::Rc;
+
+use crate::models::{Message, MsgID, SearchQuery, UsrID};
+use crate::widgets::message_bubble::{
+    BubbleSide, BubbleTheme, MessageBubble, Quote, ReactionCount,
+};
+
+// layouts of messages no longer shown are dropped once there are this many more than shown ones
+const LAYOUT_SLACK: usize = 256;
+
+// what is drawn with a message besides its own content, it depends on the messages around it
+#[derive(Clone, Default, PartialEq, Eq)]
+pub(super) struct Decoration {
+    pub(super) quote: Option<Quote>,
+    pub(super) replies: usize,
+    pub(super) reactions: Vec<ReactionCount>,
+    pub(super) edited: Option<String>,
+    pub(super) deleted: bool,
+    pub(super) time: Option<String>,
+    pub(super) continued: bool,
+    // the message is a hit of this search
+    pub(super) found: Option<Found>,
+}
+
+impl Decoration {
+    // what the message tells about itself, before the messages around it are looked at
+    pub(super) fn of(message: &Message, me: &UsrID, today: NaiveDate) -> Self {
+        Self {
+            reactions: reaction_counts(message, me),
+            edited: edit_mark(message, today),
+            deleted: message.deleted,
+            time: Some(sent_time(message)),
+            ..Default::default()
+        }
+    }
+}
+
+// a search and its query compiled once, which is shared by the bubbles of all its hits
+#[derive(Clone)]
+pub(super) struct Found {
+    pub(super) query: SearchQuery,
+    pub(super) matcher: Rc<Regex>,
+}
+
+// the matcher is compiled from the query, comparing the queries is enough
+impl PartialEq for Found {
+    fn eq(&self, other: &Self) -> bool {
+        self.query == other.query
+    }
+}
+
+impl Eq for Found {}
+
+// a bubble laid out for a width, and what it was laid out from
+pub(super) struct LaidOut {
+    content: String,
+    decoration: Decoration,
+    code_scroll: u16,
+    pub(super) code_overflow: u16,
+    pub(super) lines: Vec<Line<'static>>,
+}
+
+/// Bubbles are laid out once per message and width, not on every frame.
+///
+/// Only the bubbles in sight are painted with the current code scroll,
+/// the others are just measured, scrolling code sideways does not change their height.
+#[derive(Default)]
+pub(super) struct BubbleLayouts {
+    width: u16,
+    entries: HashMap<MsgID, LaidOut>,
+}
+
+impl BubbleLayouts {
+    // layouts of another width are of no use anymore
+    pub(super) fn set_width(&mut self, width: u16) {
+        if self.width != width {
+            self.width = width;
+            self.entries.clear();
+        }
+    }
+
+    pub(super) fn get(
+        &mut self,
+        message: &Message,
+        decoration: Decoration,
+        side: BubbleSide,
+        theme: &BubbleTheme,
+        code_scroll: u16,
+        in_sight: bool,
+    ) -> &LaidOut {
+        // a quote changes once the message it quotes is loaded, and a thread as it grows
+        let stale = match self.entries.get(&message.id) {
+            Some(laid) => {
+                laid.content != message.content
+                    || laid.decoration != decoration
+                    || (in_sight && laid.code_scroll != code_scroll)
+            }
+            None => true,
+        };
+        if stale {
+            let laid = lay_out(
+                message,
+                decoration,
+                side,
+                theme,
+                self.width,
+                code_scroll,
+                false,
+            );
+            self.entries.insert(message.id.clone(), laid);
+        }
+        &self.entries[&message.id]
+    }
+
+    // forget the messages of conversations left long ago
+    pub(super) fn retain(&mut self, messages: &[Message]) {
+        if self.entries.len() > messages.len() * 2 + LAYOUT_SLACK {
+            let ids = messages.iter().map(|m| &m.id).collect::<HashSet<_>>();
+            self.entries.retain(|id, _| ids.contains(id));
+        }
+    }
+}
+
+pub(super) fn lay_out(
+    message: &Message,
+    decoration: Decoration,
+    side: BubbleSide,
+    theme: &BubbleTheme,
+    width: u16,
+    code_scroll: u16,
+    focused: bool,
+) -> LaidOut {
+    let mut bbl = MessageBubble::new(width, &message.content, &message.from.0)
+        .with_side(side)
+        .with_theme(theme.clone())
+        .with_focus(focused)
+        .with_quote(decoration.quote.clone())
+        .with_replies(decoration.replies)
+        .with_reactions(decoration.reactions.clone())
+        .with_edited(decoration.edited.clone())
+        .with_tombstone(decoration.deleted)
+        .with_time(decoration.time.clone())
+        .with_continued(decoration.continued)
+        .with_highlight(decoration.found.as_ref().map(|f| f.matcher.clone()))
+        .with_code_scroll(code_scroll);
+    bbl.finish_painting();
+    let code_overflow = bbl.code_overflow();
+    let mut text = Text::from(bbl);
+    if side == BubbleSide::Right {
+        text.lines = text
+            .lines
+            .into_iter()
+            .map(|line| line.alignment(Alignment::Right))
+            .collect();
+    }
+    LaidOut {
+        content: message.content.clone(),
+        decoration,
+        code_scroll,
+        code_overflow,
+        lines: text.lines,
+    }
+}
+
+// the reactions on a message as the bubble shows them
+fn reaction_counts(message: &Message, me: &UsrID) -> Vec<ReactionCount> {
+    message
+        .reactions
+        .iter()
+        .map(|r| ReactionCount {
+            emoji: r.emoji.clone(),
+            count: r.users.len(),
+            mine: r.users.contains(me),
+        })
+        .collect()
+}
+
+// when the message was last edited as the bubble tells it, the time for today's edits
+// and the day for older ones, none if it was never edited or is deleted
+fn edit_mark(message: &Message, today: NaiveDate) -> Option<String> {
+    if message.deleted {
+        return None;
+    }
+    let edited_at = message.edited_at?.with_timezone(&Local);
+    let format = match edited_at.date_naive() == today {
+        true => "%H:%M",
+        false => "%b %-d",
+    };
+    Some(edited_at.format(format).to_string())
+}
+
+// when the message was sent as its prompt tells it, the day is told by the divider above
+fn sent_time(message: &Message) -> String {
+    message
+        .sent_at
+        .with_timezone(&Local)
+        .format("%H:%M")
+        .to_string()
+}
+
+// the bubbles from `tops` (rows where each bubble starts) that show up in `height` rows from `scroll`
+pub(super) fn in_sight(
+    tops: &[usize],
+    heights: &[usize],
+    scroll: usize,
+    height: usize,
+) -> Range<usize> {
+    let first = tops
+        .iter()
+        .zip(heights)
+        .position(|(top, height)| top + height > scroll)
+        .unwrap_or(tops.len());
+    let end = first + tops[first..].partition_point(|top| *top < scroll + height);
+    first..end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_bubbles_in_sight_are_picked() {
+        // a row above the bubbles, then bubbles of 4, 6, 3 and 5 rows
+        let heights = [4, 6, 3, 5];
+        let tops = [1, 5, 11, 14];
+        assert_eq!(in_sight(&tops, &heights, 0, 3), 0..1);
+        assert_eq!(in_sight(&tops, &heights, 4, 2), 0..2);
+        assert_eq!(in_sight(&tops, &heights, 5, 7), 1..3);
+        assert_eq!(in_sight(&tops, &heights, 17, 10), 3..4);
+        assert_eq!(in_sight(&tops, &heights, 30, 10), 4..4);
+    }
+}
@@ -1,3 +1,4 @@
+use chrono::{DateTime, Utc};
 use ratatui::layout::{Constraint, Direction, Layout, Rect};
 use ratatui::prelude::*;
 use ratatui::widgets::{Block, Borders, Paragraph};
@@ -5,7 +6,7 @@ use ratatui::widgets::{Block, Borders, Paragraph};
 use std::cell::RefCell;
 
 use crate::app::App;
-use crate::models::ChatSession;
+use crate::models::{ChatSession, UserState};
 use crate::widgets::hilight_area::HighlightArea;
 
 use super::UiEntity;
@@ -18,9 +19,10 @@ pub struct ChatItem {
 
 struct InternalState {
     name: String,
+    presence: Option<UserState>,
     msg_preview: String,
     msg_timestamp: String,
-    unread_msg: u32,
+    unread_msg: usize,
 }
 
 struct ItemWidget<'a> {
@@ -37,6 +39,7 @@ impl ChatItem {
             is_highlight: false,
             internal: RefCell::new(InternalState {
                 name: String::new(),
+                presence: None,
                 msg_preview: String::new(),
                 msg_timestamp: String::new(),
                 unread_msg: 0,
@@ -48,21 +51,30 @@ impl ChatItem {
         self.is_highlight = true;
     }
 
-    fn update_with_context_model(&self, _app: &App) {
+    fn update_with_context_model(&self, app: &App) {
         let mut internal = self.internal.borrow_mut();
+        let record = app.sessions_model.get_record(&self.id);
         match self.id {
-            ChatSession::Group(ref _gid) => {
-                internal.name = "Nodic Nostalgia".to_string();
-                internal.unread_msg = 23;
-                internal.msg_preview =
-                    "Neil: Welcome, Everybody. Especially you, Mr.Gump.".to_string();
-                internal.msg_timestamp = "Just Now".to_string();
+            ChatSession::Group(ref gid) => {
+                internal.name = gid.0.clone();
+                internal.presence = None;
             }
-            ChatSession::WithOther(ref _uid) => {
-                internal.name = "❤Raphina❤".to_string();
-                internal.unread_msg = 5;
-                internal.msg_preview = "Raphina: Dinner together?".to_string();
-                internal.msg_timestamp = "5m ago".to_string();
+            ChatSession::WithOther(ref uid) => {
+                internal.name = uid.0.clone();
+                internal.presence = app.sessions_model.get_presence(uid).cloned();
+            }
+        }
+
+        internal.unread_msg = record.map_or(0, |r| r.unread_msg);
+        match record.and_then(|r| r.last_message.as_ref()) {
+            Some(msg) => {
+                let first_line = msg.content.lines().next().unwrap_or_default();
+                internal.msg_preview = format!("{}: {}", msg.from.0, first_line);
+                internal.msg_timestamp = relative_time(msg.sent_at, Utc::now());
+            }
+            None => {
+                internal.msg_preview = String::from("No messages yet");
+                internal.msg_timestamp = String::new();
             }
         }
     }
@@ -82,13 +94,24 @@ impl ChatItem {
             text_style = Style::default().fg(Color::White);
         }
 
-        let name_sec = Paragraph::new(name)
+        let mut name_line = Vec::new();
+        if let Some(ref presence) = internal.presence {
+            name_line.push(presence_dot(presence));
+        }
+        name_line.push(Span::raw(name));
+
+        let unread_msg = match internal.unread_msg {
+            0 => String::new(),
+            n => n.to_string(),
+        };
+
+        let name_sec = Paragraph::new(Line::from(name_line))
             .block(Block::default().borders(Borders::NONE))
             .style(text_style)
             .alignment(Alignment::Left)
             .wrap(ratatui::widgets::Wrap { trim: true });
 
-        let unread_msg_sec = Paragraph::new(internal.unread_msg.to_string().clone())
+        let unread_msg_sec = Paragraph::new(unread_msg)
             .block(Block::default().borders(Borders::NONE))
             .style(Style::default().fg(Color::Red))
             .alignment(Alignment::Right)
@@ -115,6 +138,31 @@ impl ChatItem {
     }
 }
 
+fn presence_dot<'a>(presence: &UserState) -> Span<'a> {
+    let (dot, color) = match presence {
+        UserState::Online => ("● ", Color::Green),
+        UserState::Busy => ("● ", Color::Red),
+        UserState::Idle => ("● ", Color::Yellow),
+        UserState::Offline => ("○ ", Color::DarkGray),
+        UserState::Cusomized(_) => ("◆ ", Color::Cyan),
+    };
+    Span::styled(dot, Style::default().fg(color))
+}
+
+// how long ago, in the short form used by the session list
+fn relative_time(time: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    let elapsed = now.signed_duration_since(time);
+    if elapsed.num_minutes() < 1 {
+        String::from("Just Now")
+    } else if elapsed.num_hours() < 1 {
+        format!("{}m ago", elapsed.num_minutes())
+    } else if elapsed.num_days() < 1 {
+        format!("{}h ago", elapsed.num_hours())
+    } else {
+        time.format("%b %d").to_string()
+    }
+}
+
 impl UiEntity for ChatItem {
     fn draw(&self, app: &App, frame: &mut ratatui::prelude::Frame, area: Rect) {
         let item = self.get_ui(app, area);
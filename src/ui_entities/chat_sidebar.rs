@@ -1,14 +1,15 @@
 use crossterm::event::KeyCode;
 use ratatui::layout::Layout;
 use ratatui::prelude::*;
+use ratatui::widgets::block::{Position, Title};
 use ratatui::widgets::{Block, Borders};
 
 use std::cell::RefCell;
 use std::rc::Rc;
 
-use crate::action::{Action, MessagesModelAction, StateModelAction};
+use crate::action::{Action, MessagesModelAction, SessionsModelAction, StateModelAction};
 use crate::app::App;
-use crate::models::{state::StateModel, RemoteData};
+use crate::models::state::StateModel;
 use crate::tio::TerminalEvent;
 
 use super::chat_item::ChatItem;
@@ -53,16 +54,10 @@ impl LeftSessionList {
     pub fn with_context_model(self, app: &App) -> Self {
         let mut ret = self;
         let mut items = Vec::new();
-        match app.sessions_model.get_model_data() {
-            RemoteData::Success(data) => {
-                data.0
-                    .iter()
-                    .for_each(|record| items.push(ChatItem::new(record.session.clone())));
-                assert_ne!(items.len(), 0);
-            }
-            _ => {
-                assert_eq!(items.len(), 0);
-            }
+        if let Some(data) = app.sessions_model.get_model_data().data() {
+            data.0
+                .iter()
+                .for_each(|record| items.push(ChatItem::new(record.session.clone())));
         }
 
         ret.items = items;
@@ -80,16 +75,10 @@ impl LeftSessionList {
     // TODO: setup highligth for chat item.
     pub fn update_with_context_model(&mut self, app: &App) {
         let mut items = Vec::new();
-        match app.sessions_model.get_model_data() {
-            RemoteData::Success(data) => {
-                data.0
-                    .iter()
-                    .for_each(|record| items.push(ChatItem::new(record.session.clone())));
-                assert_ne!(items.len(), 0);
-            }
-            _ => {
-                assert_eq!(items.len(), 0);
-            }
+        if let Some(data) = app.sessions_model.get_model_data().data() {
+            data.0
+                .iter()
+                .for_each(|record| items.push(ChatItem::new(record.session.clone())));
         }
 
         if let Some(select_idx) = self.internal.borrow().selected() {
@@ -149,13 +138,24 @@ impl UiEntity for LeftSessionList {
             StateModel::Chats => Style::new().fg(Color::Green),
             _ => Style::default(),
         };
-        frame.render_widget(
-            Block::default()
-                .borders(Borders::ALL)
-                .title("Chats")
-                .border_style(bdr_stl),
-            area,
-        );
+        let data = app.sessions_model.get_model_data();
+        let mut block = Block::default()
+            .borders(Borders::ALL)
+            .title("Chats")
+            .border_style(bdr_stl);
+        if data.is_loading() {
+            block = block.title(
+                Title::from(Span::styled(self.meta_data.spinner(), Style::new().dim()))
+                    .alignment(Alignment::Right),
+            );
+        }
+        if data.error().is_some() {
+            block = block.title(
+                Title::from(Span::styled(" ⚠ offline · r to retry ", Style::new().red()))
+                    .position(Position::Bottom),
+            );
+        }
+        frame.render_widget(block, area);
     }
 
     fn make_blueprints<'a, 'b>(
@@ -198,6 +198,9 @@ impl UiEntity for LeftSessionList {
                 self.prev();
                 TerminalEventResult::Handled(Action::Nop)
             }
+            TerminalEvent::Key(k) if k.code == KeyCode::Char('r') => {
+                TerminalEventResult::Handled(Action::SessionsModel(SessionsModelAction::Reload))
+            }
             TerminalEvent::Key(k) if k.code == KeyCode::Enter => {
                 let internal = self.internal.borrow();
                 if let Some(offset) = internal.selected() {
@@ -206,6 +209,9 @@ impl UiEntity for LeftSessionList {
                         Action::MessagesModel(MessagesModelAction::SetBind(
                             self.items[offset].id.clone(),
                         )),
+                        Action::SessionsModel(SessionsModelAction::MarkRead(
+                            self.items[offset].id.clone(),
+                        )),
                     ]))
                 } else {
                     TerminalEventResult::Handled(Action::Nop)
@@ -0,0 +1,478 @@
+/// InputField is the message composer docked under the message viewer.
+///
+/// - Enter sends the message, Shift-Enter or Alt-Enter inserts a newline.
+/// - Esc gives the focus back to the message viewer, or first drops the message being replied to.
+/// - While a message is edited the composer holds its content, Enter saves it
+///   and Esc drops the edit with what was written before put back.
+/// - The composer under an open thread posts into the thread and gives the focus back to it.
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use ratatui::prelude::*;
+use ratatui::widgets::block::Title;
+use ratatui::widgets::{Block, Borders, Paragraph};
+use unicode_width::UnicodeWidthStr;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::action::{Action, MessagesModelAction, StateModelAction};
+use crate::app::App;
+use crate::models::state::StateModel;
+use crate::models::MsgID;
+use crate::tio::TerminalEvent;
+use crate::widgets::markdown;
+use crate::widgets::message_bubble::{author_color, ellipsize};
+
+use super::{TerminalEventResult, UiEntity, UiId, UiMetaData, UiTag};
+
+// how many lines the composer grows to before it starts scrolling
+const MAX_VISIBLE_LINES: u16 = 5;
+
+#[derive(Default)]
+struct InternalState {
+    buffer: String,
+    // cursor is counted in chars, not in bytes
+    cursor: usize,
+    // the message being edited, and what was written before it was picked for editing
+    editing: Option<MsgID>,
+    draft: Option<(String, usize)>,
+}
+
+impl InternalState {
+    fn byte_offset(&self, char_idx: usize) -> usize {
+        self.buffer
+            .char_indices()
+            .nth(char_idx)
+            .map(|(offset, _)| offset)
+            .unwrap_or(self.buffer.len())
+    }
+
+    fn char_count(&self) -> usize {
+        self.buffer.chars().count()
+    }
+
+    fn char_at(&self, char_idx: usize) -> Option<char> {
+        self.buffer.chars().nth(char_idx)
+    }
+
+    fn insert(&mut self, ch: char) {
+        let offset = self.byte_offset(self.cursor);
+        self.buffer.insert(offset, ch);
+        self.cursor += 1;
+    }
+
+    fn delete_range(&mut self, from: usize, to: usize) {
+        let (from, to) = (self.byte_offset(from), self.byte_offset(to));
+        self.buffer.replace_range(from..to, "");
+    }
+
+    fn backspace(&mut self) {
+        if self.cursor > 0 {
+            self.delete_range(self.cursor - 1, self.cursor);
+            self.cursor -= 1;
+        }
+    }
+
+    fn delete(&mut self) {
+        if self.cursor < self.char_count() {
+            self.delete_range(self.cursor, self.cursor + 1);
+        }
+    }
+
+    fn delete_word_before(&mut self) {
+        let start = self.word_start_before(self.cursor);
+        self.delete_range(start, self.cursor);
+        self.cursor = start;
+    }
+
+    fn move_left(&mut self) {
+        self.cursor = self.cursor.saturating_sub(1);
+    }
+
+    fn move_right(&mut self) {
+        self.cursor = std::cmp::min(self.cursor + 1, self.char_count());
+    }
+
+    fn move_word_left(&mut self) {
+        self.cursor = self.word_start_before(self.cursor);
+    }
+
+    fn move_word_right(&mut self) {
+        let len = self.char_count();
+        let mut idx = self.cursor;
+        while idx < len && self.char_at(idx).is_some_and(char::is_whitespace) {
+            idx += 1;
+        }
+        while idx < len && self.char_at(idx).is_some_and(|c| !c.is_whitespace()) {
+            idx += 1;
+        }
+        self.cursor = idx;
+    }
+
+    fn move_line_start(&mut self) {
+        let (row, _) = self.cursor_row_col();
+        self.cursor = self.line_start(row);
+    }
+
+    fn move_line_end(&mut self) {
+        let (row, _) = self.cursor_row_col();
+        self.cursor = self.line_start(row) + self.line(row).chars().count();
+    }
+
+    fn word_start_before(&self, from: usize) -> usize {
+        let mut idx = from;
+        while idx > 0 && self.char_at(idx - 1).is_some_and(char::is_whitespace) {
+            idx -= 1;
+        }
+        while idx > 0 && self.char_at(idx - 1).is_some_and(|c| !c.is_whitespace()) {
+            idx -= 1;
+        }
+        idx
+    }
+
+    fn line(&self, row: usize) -> &str {
+        self.buffer.split('\n').nth(row).unwrap_or_default()
+    }
+
+    fn line_start(&self, row: usize) -> usize {
+        self.buffer
+            .split('\n')
+            .take(row)
+            .map(|l| l.chars().count() + 1)
+            .sum()
+    }
+
+    fn line_count(&self) -> usize {
+        self.buffer.split('\n').count()
+    }
+
+    // row in lines and column in chars of the cursor
+    fn cursor_row_col(&self) -> (usize, usize) {
+        let before = &self.buffer[..self.byte_offset(self.cursor)];
+        let row = before.matches('\n').count();
+        let col = before
+            .rsplit('\n')
+            .next()
+            .unwrap_or_default()
+            .chars()
+            .count();
+        (row, col)
+    }
+
+    fn take(&mut self) -> String {
+        self.cursor = 0;
+        std::mem::take(&mut self.buffer)
+    }
+
+    // load the content of the message picked for editing, or put the draft back once done with it
+    fn edit(&mut self, editing: Option<&MsgID>, content: impl FnOnce() -> String) {
+        if self.editing.as_ref() == editing {
+            return;
+        }
+        if self.editing.is_none() {
+            let cursor = self.cursor;
+            self.draft = Some((self.take(), cursor));
+        }
+        self.editing = editing.cloned();
+        match self.editing {
+            Some(_) => {
+                self.buffer = content();
+                self.cursor = self.char_count();
+            }
+            None => {
+                let (buffer, cursor) = self.draft.take().unwrap_or_default();
+                self.buffer = buffer;
+                self.cursor = cursor;
+            }
+        }
+    }
+}
+
+// where the messages written in a composer go
+#[derive(Default, Clone, Copy, PartialEq, Eq)]
+pub enum ComposerTarget {
+    #[default]
+    Conversation,
+    Thread,
+}
+
+#[derive(Default)]
+pub struct InputField {
+    id: UiId,
+    tag: Option<UiTag>,
+    meta_data: Rc<UiMetaData>,
+    target: ComposerTarget,
+    internal: RefCell<InternalState>,
+}
+
+impl InputField {
+    pub fn with_metadata(self, meta: Rc<UiMetaData>) -> Self {
+        let mut ret = self;
+        ret.id = meta.next_id();
+        ret.meta_data = meta;
+        ret
+    }
+
+    pub fn with_tag(self, tag: UiTag) -> Self {
+        let mut ret = self;
+        ret.tag = Some(tag);
+        ret.meta_data.set_tag(tag, ret.id);
+        ret
+    }
+
+    pub fn with_target(self, target: ComposerTarget) -> Self {
+        let mut ret = self;
+        ret.target = target;
+        ret
+    }
+
+    /// Height needed to draw the composer, including its borders
+    pub fn height(&self) -> u16 {
+        let lines = self.internal.borrow().line_count() as u16;
+        std::cmp::min(lines, MAX_VISIBLE_LINES) + 2
+    }
+
+    // the conversation composer follows the message picked for editing in the model
+    fn sync_editing(&self, app: &App) {
+        if self.target != ComposerTarget::Conversation {
+            return;
+        }
+        let editing = app.messages_model.get_editing();
+        self.internal.borrow_mut().edit(editing, || {
+            editing
+                .and_then(|id| app.messages_model.get_message(id))
+                .map(|message| message.content.clone())
+                .unwrap_or_default()
+        });
+    }
+
+    fn handle_key(&mut self, key: KeyEvent, app: &App) -> TerminalEventResult {
+        self.sync_editing(app);
+        let mut internal = self.internal.borrow_mut();
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+
+        match key.code {
+            KeyCode::Esc if internal.editing.is_some() => {
+                return TerminalEventResult::Handled(Action::MessagesModel(
+                    MessagesModelAction::Editing(None),
+                ));
+            }
+            KeyCode::Esc
+                if self.target == ComposerTarget::Conversation
+                    && app.messages_model.get_replying_to().is_some() =>
+            {
+                return TerminalEventResult::Handled(Action::MessagesModel(
+                    MessagesModelAction::ReplyTo(None),
+                ));
+            }
+            KeyCode::Esc => {
+                let back = match self.target {
+                    ComposerTarget::Conversation => StateModel::Messages,
+                    ComposerTarget::Thread => StateModel::Thread,
+                };
+                return TerminalEventResult::Handled(Action::StateModel(
+                    StateModelAction::SetActive(back),
+                ));
+            }
+            KeyCode::Enter if alt || key.modifiers.contains(KeyModifiers::SHIFT) => {
+                internal.insert('\n')
+            }
+            KeyCode::Enter => {
+                if internal.buffer.trim().is_empty() {
+                    return TerminalEventResult::Handled(Action::Nop);
+                }
+                let content = internal.take();
+                if let Some(id) = internal.editing.clone() {
+                    return TerminalEventResult::Handled(Action::MessagesModel(
+                        MessagesModelAction::Edit(id, content),
+                    ));
+                }
+                let action = match self.target {
+                    ComposerTarget::Conversation => MessagesModelAction::Send(content),
+                    ComposerTarget::Thread => MessagesModelAction::SendInThread(content),
+                };
+                return TerminalEventResult::Handled(Action::MessagesModel(action));
+            }
+            KeyCode::Left if ctrl || alt => internal.move_word_left(),
+            KeyCode::Right if ctrl || alt => internal.move_word_right(),
+            KeyCode::Left => internal.move_left(),
+            KeyCode::Right => internal.move_right(),
+            KeyCode::Home => internal.move_line_start(),
+            KeyCode::End => internal.move_line_end(),
+            KeyCode::Backspace if ctrl || alt => internal.delete_word_before(),
+            KeyCode::Backspace => internal.backspace(),
+            KeyCode::Delete => internal.delete(),
+            KeyCode::Char('a') if ctrl => internal.move_line_start(),
+            KeyCode::Char('e') if ctrl => internal.move_line_end(),
+            KeyCode::Char('w') if ctrl => internal.delete_word_before(),
+            KeyCode::Char('b') if alt => internal.move_word_left(),
+            KeyCode::Char('f') if alt => internal.move_word_right(),
+            KeyCode::Char(_) if ctrl || alt => {
+                return TerminalEventResult::NotHandled(TerminalEvent::Key(key));
+            }
+            KeyCode::Char(c) => internal.insert(c),
+            _ => return TerminalEventResult::NotHandled(TerminalEvent::Key(key)),
+        }
+
+        TerminalEventResult::Handled(Action::Nop)
+    }
+}
+
+impl UiEntity for InputField {
+    fn draw(&self, app: &App, frame: &mut Frame, area: Rect) {
+        self.sync_editing(app);
+        let is_active = app.state_model
+            == match self.target {
+                ComposerTarget::Conversation => StateModel::Composer,
+                ComposerTarget::Thread => StateModel::ThreadComposer,
+            };
+        let bdr_stl = if is_active {
+            Style::new().fg(Color::Green)
+        } else {
+            Style::default()
+        };
+        let mut block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(bdr_stl)
+            .title("Message");
+        let replying_to = match self.target {
+            ComposerTarget::Conversation => app.messages_model.get_replying_to(),
+            ComposerTarget::Thread => None,
+        };
+        // what is left of the top border after the corners and the first title
+        let room = area.width.saturating_sub(2 + "Message".width() as u16 + 1);
+        let internal = self.internal.borrow();
+        if internal.editing.is_some() {
+            block = block.title(Title::from(Line::styled(
+                ellipsize("▍ editing message · esc to cancel ", room),
+                Style::new().dim(),
+            )));
+        } else if let Some(parent) = replying_to {
+            block = block.title(Title::from(reply_title(app, parent, room)));
+        }
+        let inner_area = block.inner(area);
+
+        let (row, col) = internal.cursor_row_col();
+        let line = internal.line(row);
+        let cursor_x = line.chars().take(col).collect::<String>().width() as u16;
+        let cursor_y = row as u16;
+
+        // keep the cursor inside the visible part of the composer
+        let scroll_y = cursor_y.saturating_sub(inner_area.height.saturating_sub(1));
+        let scroll_x = cursor_x.saturating_sub(inner_area.width.saturating_sub(1));
+
+        let paragraph = Paragraph::new(internal.buffer.clone())
+            .block(block)
+            .scroll((scroll_y, scroll_x));
+        frame.render_widget(paragraph, area);
+
+        if is_active {
+            frame.set_cursor(
+                inner_area.x + cursor_x - scroll_x,
+                inner_area.y + cursor_y - scroll_y,
+            );
+        }
+    }
+
+    fn handle_terminal_event(&mut self, event: TerminalEvent, app: &App) -> TerminalEventResult {
+        match event {
+            TerminalEvent::Key(key) => self.handle_key(key, app),
+            _ => TerminalEventResult::NotHandled(event),
+        }
+    }
+}
+
+// who and what the message being written answers, in at most `width` cells,
+// the excerpt is cut short first
+fn reply_title<'a>(app: &App, parent: &MsgID, width: u16) -> Line<'a> {
+    const PREFIX: &str = "▍ replying to ";
+    const SUFFIX: &str = " · esc to cancel ";
+    let quote = Style::new().add_modifier(Modifier::DIM | Modifier::ITALIC);
+    let (author, excerpt) = match app.messages_model.get_message(parent) {
+        Some(message) => (
+            message.from.0.clone(),
+            format!(" {}", markdown::excerpt(&message.content)),
+        ),
+        None => (parent.0.clone(), String::new()),
+    };
+
+    let fixed = (PREFIX.width() + author.width() + SUFFIX.width()) as u16;
+    if fixed + 2 > width {
+        return Line::styled(ellipsize(&format!("{}{}", PREFIX, author), width), quote);
+    }
+    Line::from(vec![
+        Span::styled(PREFIX, Style::new().dim()),
+        Span::styled(
+            author.clone(),
+            Style::new()
+                .fg(author_color(&author))
+                .add_modifier(Modifier::BOLD),
+        ),
+        Span::styled(ellipsize(&excerpt, width - fixed), quote),
+        Span::styled(SUFFIX, Style::new().dim()),
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state_with(text: &str) -> InternalState {
+        let mut state = InternalState::default();
+        text.chars().for_each(|c| state.insert(c));
+        state
+    }
+
+    #[test]
+    fn test_insert_and_delete() {
+        let mut state = state_with("héllo");
+        state.move_left();
+        state.backspace();
+        assert_eq!(state.buffer, "hélo");
+        state.delete();
+        assert_eq!(state.buffer, "hél");
+        assert_eq!(state.cursor, 3);
+    }
+
+    #[test]
+    fn test_word_movement() {
+        let mut state = state_with("hello  big world");
+        state.move_word_left();
+        assert_eq!(state.cursor, 11);
+        state.move_word_left();
+        assert_eq!(state.cursor, 7);
+        state.delete_word_before();
+        assert_eq!(state.buffer, "big world");
+        state.move_word_right();
+        assert_eq!(state.cursor, 3);
+    }
+
+    #[test]
+    fn test_multiline_cursor() {
+        let mut state = state_with("first\nsecond");
+        assert_eq!(state.cursor_row_col(), (1, 6));
+        state.move_line_start();
+        assert_eq!(state.cursor_row_col(), (1, 0));
+        state.move_left();
+        assert_eq!(state.cursor_row_col(), (0, 5));
+        assert_eq!(state.take(), "first\nsecond");
+        assert_eq!(state.cursor, 0);
+    }
+
+    #[test]
+    fn test_editing_keeps_the_draft() {
+        let (a, b) = (MsgID("a".to_string()), MsgID("b".to_string()));
+        let mut state = state_with("half a thought");
+        state.move_word_left();
+
+        state.edit(Some(&a), || String::from("sent before"));
+        assert_eq!(state.buffer, "sent before");
+        assert_eq!(state.cursor, 11);
+        state.edit(Some(&b), || String::from("another"));
+        assert_eq!(state.buffer, "another");
+
+        state.edit(None, String::new);
+        assert_eq!(state.buffer, "half a thought");
+        assert_eq!(state.cursor, 7);
+    }
+}
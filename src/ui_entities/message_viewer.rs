@@ -1,37 +1,352 @@
-use crossterm::event::KeyCode;
+use chrono::{Datelike, Local, NaiveDate};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 use ratatui::prelude::*;
 use ratatui::text::Text;
+use ratatui::widgets::block::{Position, Title};
 use ratatui::widgets::{
-    Block, Borders, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState,
+    Block, Borders, Clear, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState,
 };
 
+use unicode_width::UnicodeWidthStr;
+
 use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
 use std::rc::Rc;
+use std::time::{Duration, Instant};
 
-use crate::action::Action;
+use crate::action::{Action, MessagesModelAction, StateModelAction};
 use crate::app::App;
+use crate::models::messages::Search;
 use crate::models::state::StateModel;
-use crate::models::RemoteData;
-use crate::tio::TerminalEvent;
-use crate::widgets::message_bubble::MessageBubble;
+use crate::models::{ChatSession, DeliveryStatus, Message, MsgID, Receiver, RemoteData, UsrID};
+use crate::tio::{TerminalEvent, Tio};
+use crate::widgets::markdown;
+use crate::widgets::message_bubble::{ellipsize, BubbleSide, BubbleTheme, MessageBubble, Quote};
 
+use super::bubble_layouts::{in_sight, lay_out, BubbleLayouts, Decoration, Found};
+use super::reaction_picker::{Picking, ReactionPicker};
+use super::search_bar::{SearchBar, Typed};
 use super::{TerminalEventResult, UiEntity, UiId, UiMetaData, UiTag};
 
+const NOTICE_TIMEOUT: Duration = Duration::from_secs(3);
+const SELECTION_HINT: &str =
+    " r reply · p original · y copy · + react · e edit · d delete · s resend · ⏎ details · esc ";
+const GROUP_SELECTION_HINT: &str =
+    " r reply · t thread · p original · y copy · + react · e edit · d delete · s resend · ⏎ details · esc ";
+
+// messages of one author this close to each other are drawn under one prompt
+const GROUP_WINDOW: chrono::Duration = chrono::Duration::minutes(5);
+
+// how a message follows the one before it in the timeline
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq)]
+struct Flow {
+    // the day it was sent, if the one before was sent on another day
+    divider: Option<NaiveDate>,
+    // it goes on from the one before, by the same author a moment earlier
+    continued: bool,
+}
+
+// the quote of the message `message` replies to, looked up among the loaded ones
+fn quote_of(message: &Message, loaded: &HashMap<&MsgID, &Message>) -> Option<Quote> {
+    let parent = message.reply_to.as_ref()?;
+    Some(match loaded.get(parent) {
+        Some(parent) if parent.deleted => Quote::Message {
+            author: parent.from.0.clone(),
+            excerpt: String::from("message deleted"),
+        },
+        Some(parent) => Quote::Message {
+            author: parent.from.0.clone(),
+            excerpt: markdown::excerpt(&parent.content),
+        },
+        None => Quote::Missing,
+    })
+}
+
+// the divider over the first message of a day, "Today", "Yesterday" or the date
+fn day_label(day: NaiveDate, today: NaiveDate) -> String {
+    match (today - day).num_days() {
+        0 => String::from("Today"),
+        1 => String::from("Yesterday"),
+        _ if day.year() == today.year() => day.format("%a %-d %b").to_string(),
+        _ => day.format("%a %-d %b %Y").to_string(),
+    }
+}
+
+// how each message follows the one before it in the timeline,
+// the replies of threads are not in it and leave no trace
+fn flow(messages: &[Message]) -> Vec<Flow> {
+    let mut flows = Vec::with_capacity(messages.len());
+    let mut before: Option<&Message> = None;
+    for m in messages {
+        if !in_timeline(m) {
+            flows.push(Flow::default());
+            continue;
+        }
+        let day = m.sent_at.with_timezone(&Local).date_naive();
+        let same_day = before.is_some_and(|b| b.sent_at.with_timezone(&Local).date_naive() == day);
+        flows.push(Flow {
+            divider: (!same_day).then_some(day),
+            continued: same_day
+                && before
+                    .is_some_and(|b| b.from == m.from && m.sent_at - b.sent_at <= GROUP_WINDOW),
+        });
+        before = Some(m);
+    }
+    flows
+}
+
+// replies of a thread are shown next to the conversation, not in it
+pub(super) fn in_timeline(message: &Message) -> bool {
+    message.thread.is_none()
+}
+
+// the first message from `idx` on, going forward or backward, that is in the timeline
+fn timeline_from(messages: &[Message], idx: usize, forward: bool) -> Option<usize> {
+    if forward {
+        (idx..messages.len()).find(|&i| in_timeline(&messages[i]))
+    } else {
+        (0..=idx.min(messages.len().checked_sub(1)?))
+            .rev()
+            .find(|&i| in_timeline(&messages[i]))
+    }
+}
+
+// how many replies the thread started by each message has
+fn thread_replies(messages: &[Message]) -> HashMap<&MsgID, usize> {
+    let mut replies = HashMap::new();
+    for root in messages.iter().filter_map(|m| m.thread.as_ref()) {
+        *replies.entry(root).or_default() += 1;
+    }
+    replies
+}
+
 #[derive(Default)]
 struct InternalState {
-    messages: Vec<(String, String)>,
+    // shown instead of the messages while there are none to show
+    placeholder: Option<String>,
     title: String,
+    // the conversation drawn last time and its newest message
+    session: Option<ChatSession>,
+    last_id: Option<MsgID>,
+    // whether the viewport sticks to the newest message,
+    // and how many came in below it while it does not
+    follow: bool,
+    unseen: usize,
+    // as measured by the last draw
+    view_height: usize,
+    total_rows: usize,
+    in_sight: Range<usize>,
+    // the message the cursor is on while selecting, kept by id to survive reflow and new messages,
+    // and by index to land next to it once it is gone
+    selecting: bool,
+    focus: Option<MsgID>,
+    focus_idx: usize,
+    // the cursor moved, the next draw scrolls it into sight
+    reveal_focus: bool,
+    details: bool,
+    // the reaction picker, open from + until an emoji is picked
+    picker: Option<ReactionPicker>,
+    // the message d was pressed on once, pressing it again deletes it
+    deleting: Option<MsgID>,
+    // the day the labels are relative to, it moves on with the clock ticks
+    today: Option<NaiveDate>,
+    // the search bar, open from / until it is closed with Esc
+    search: Option<SearchBar>,
+    // the message to put the cursor on once it is loaded, when jumping to the original of a reply
+    jump_to: Option<MsgID>,
+    // a short note on what an action did, shown for a moment
+    notice: Option<(String, Instant)>,
+    // how many failures of the model have been told already
+    failures_told: usize,
+    // the store stops saving once, that is told once too
+    store_error_told: bool,
+    // to keep the viewport still when older messages are loaded above it
+    first_id: Option<MsgID>,
+    // none if this is not the conversation drawn last time
+    prepended: Option<usize>,
+    top_rows: usize,
     pub vertical_scroll_state: ScrollbarState,
     pub vertical_scroll: usize,
     pub horizontal_scroll_state: ScrollbarState,
     pub horizontal_scroll: usize,
 }
 
+impl InternalState {
+    fn max_scroll(&self) -> usize {
+        self.total_rows.saturating_sub(self.view_height)
+    }
+
+    fn scroll_down(&mut self, rows: usize) {
+        self.vertical_scroll = (self.vertical_scroll + rows).min(self.max_scroll());
+        if self.vertical_scroll == self.max_scroll() {
+            self.scroll_to_bottom();
+        }
+    }
+
+    // returns false if the viewport was at the top already
+    fn scroll_up(&mut self, rows: usize) -> bool {
+        if self.vertical_scroll == 0 {
+            return false;
+        }
+        self.follow = false;
+        self.vertical_scroll = self.vertical_scroll.saturating_sub(rows);
+        true
+    }
+
+    fn scroll_to_bottom(&mut self) {
+        self.follow = true;
+        self.unseen = 0;
+        self.vertical_scroll = self.max_scroll();
+    }
+
+    // a half page, at least one row
+    fn half_page(&self) -> usize {
+        (self.view_height / 2).max(1)
+    }
+
+    fn focus_on(&mut self, messages: &[Message], idx: usize) {
+        if let Some(message) = messages.get(idx) {
+            self.focus = Some(message.id.clone());
+            self.focus_idx = idx;
+            self.reveal_focus = true;
+        }
+    }
+
+    // the cursor is put on the newest message in sight, or the newest one if none is
+    fn start_selecting(&mut self, messages: &[Message]) {
+        if messages.is_empty() {
+            return;
+        }
+        let idx = match self.in_sight.end {
+            0 => messages.len() - 1,
+            end => end.min(messages.len()) - 1,
+        };
+        let Some(idx) =
+            timeline_from(messages, idx, false).or_else(|| timeline_from(messages, idx, true))
+        else {
+            return;
+        };
+        self.selecting = true;
+        self.focus_on(messages, idx);
+    }
+
+    fn stop_selecting(&mut self) {
+        self.selecting = false;
+        self.details = false;
+        self.picker = None;
+        self.deleting = None;
+        self.focus = None;
+        self.jump_to = None;
+    }
+
+    // put the cursor on the message the focused one replies to, none if it is not loaded yet
+    fn jump_to_original(&mut self, messages: &[Message], parent: &MsgID) -> bool {
+        match messages.iter().position(|m| m.id == *parent) {
+            Some(idx) => {
+                self.focus_on(messages, idx);
+                true
+            }
+            None => {
+                self.jump_to = Some(parent.clone());
+                false
+            }
+        }
+    }
+
+    fn open_search(&mut self) {
+        self.search = Some(SearchBar::open(self.search.take()));
+    }
+
+    // the hits as the messages are now, the cursor goes to the newest one once there is one
+    fn update_search(&mut self, messages: &[Message], found: Option<&Search>) {
+        let Some(bar) = self.search.as_mut() else {
+            return;
+        };
+        if let Some(idx) = bar.update(messages, found) {
+            self.selecting = true;
+            self.focus_on(messages, idx);
+        }
+    }
+
+    // put the cursor on the hit older or newer than the one it is on
+    fn next_hit(&mut self, messages: &[Message], newer: bool) -> TerminalEventResult {
+        let nop = TerminalEventResult::Handled(Action::Nop);
+        let Some(bar) = self.search.as_mut() else {
+            return nop;
+        };
+        let id = match bar.step(newer) {
+            Ok(id) => id,
+            Err(notice) => {
+                self.notify(notice);
+                return nop;
+            }
+        };
+        // a match still being loaded is given up for this one
+        self.jump_to = None;
+        self.selecting = true;
+        if self.jump_to_original(messages, &id) {
+            return nop;
+        }
+        self.notify("loading older messages to get to the match");
+        TerminalEventResult::Handled(Action::MessagesModel(MessagesModelAction::FetchOlder))
+    }
+
+    fn tick(&mut self) {
+        self.today = Some(Local::now().date_naive());
+    }
+
+    fn notify(&mut self, notice: impl Into<String>) {
+        self.notice = Some((notice.into(), Instant::now()));
+    }
+
+    // the index of the focused message, if it is gone or not in the timeline
+    // the one now at its place, or the one before it
+    fn resolve_focus(&mut self, messages: &[Message]) -> Option<usize> {
+        if !self.selecting {
+            return None;
+        }
+        let idx = self
+            .focus
+            .as_ref()
+            .and_then(|id| messages.iter().position(|m| m.id == *id))
+            .unwrap_or(self.focus_idx);
+        let Some(idx) =
+            timeline_from(messages, idx, true).or_else(|| timeline_from(messages, idx, false))
+        else {
+            self.stop_selecting();
+            return None;
+        };
+        self.focus = Some(messages[idx].id.clone());
+        self.focus_idx = idx;
+        Some(idx)
+    }
+
+    // the message the cursor is on, by its id, the index may be from before messages came in
+    fn focused<'m>(&self, messages: &'m [Message]) -> Option<&'m Message> {
+        let focus = self.focus.as_ref()?;
+        messages.iter().find(|m| m.id == *focus)
+    }
+
+    // scroll just as far as needed to show the rows from `top` to `bottom`
+    fn reveal(&mut self, top: usize, bottom: usize) {
+        if bottom > self.vertical_scroll + self.view_height {
+            self.vertical_scroll = bottom.saturating_sub(self.view_height);
+        }
+        if top < self.vertical_scroll {
+            self.vertical_scroll = top;
+        }
+        self.follow = false;
+    }
+}
+
 #[derive(Default)]
 pub struct RightSpace {
     id: UiId,
     tag: Option<UiTag>,
     meta_data: Rc<UiMetaData>,
+    bubble_theme: BubbleTheme,
+    layouts: RefCell<BubbleLayouts>,
     internal_state: RefCell<InternalState>,
 }
 
@@ -44,43 +359,13 @@ impl RightSpace {
     }
 
     pub fn with_context_model(self, app: &App) -> Self {
-        let ret = self;
-        let mut messages = Vec::<(String, String)>::new();
-        let title;
-        // let default_rs = Self::default();
-        if let Some(session_name) = app.messages_model.bind.clone() {
-            title = format!("Messages from {:?}", session_name);
-            match app.messages_model.get_model_data() {
-                RemoteData::Success(data) => data.iter().for_each(|m| {
-                    messages.push((
-                        "kevin".to_string(),
-                        format!("[message content of {:?}]: \n", m),
-                    ))
-                }),
-                _ => {
-                    messages.push(("PIXAL".to_string(), String::from("messages is loading...")));
-                }
-            }
-        } else {
-            title = String::from("No Session Selected");
-        }
-
-        // let mut long_text = "abcdefg".chars().cycle().take(200).collect::<String>();
-        // long_text.push('\n');
-        // let long_text_len = long_text.len();
-        // messages.push(long_text);
-
-        let mut internal = ret.internal_state.borrow_mut();
-        internal.title = title;
-        internal.messages = messages;
-        internal.vertical_scroll_state = internal
-            .vertical_scroll_state
-            .content_length(internal.messages.len());
-        // internal.horizontal_scroll_state = internal
-        //     .horizontal_scroll_state
-        //     .content_length(long_text_len);
-        drop(internal);
+        self.update_with_context_model(app);
+        self
+    }
 
+    pub fn with_bubble_theme(self, theme: BubbleTheme) -> Self {
+        let mut ret = self;
+        ret.bubble_theme = theme;
         ret
     }
 
@@ -92,40 +377,102 @@ impl RightSpace {
     }
 
     fn update_with_context_model(&self, app: &App) {
-        let mut messages = Vec::new();
+        let mut placeholder = None;
+        let mut first_id = None;
+        let mut prepended = None;
+        let mut last_id = None;
+        let mut arrived = 0;
+        let mut mine = false;
         let title;
-        if let Some(session_name) = app.messages_model.bind.clone() {
+        let session = app.messages_model.bind.clone();
+        let same_session = session == self.internal_state.borrow().session;
+        if let Some(session_name) = session.clone() {
             title = format!("Messages from {:?}", session_name);
             match app.messages_model.get_model_data() {
-                RemoteData::Success(data) => data.iter().for_each(|m| {
-                    messages.push((
-                        "kevin".to_string(),
-                        format!("[message content of {:?}]", m),
-                        // "hello".to_string(),
-                    ))
-                }),
-                _ => {
-                    messages.push(("PIXAL".to_string(), String::from("messages is loading...")));
-                }
+                RemoteData::Failed {
+                    last_good: None, ..
+                } => placeholder = Some(String::from("messages failed to load")),
+                data => match data.data() {
+                    Some(data) => {
+                        // the old first message is not first anymore, older ones came in
+                        if let Some(ref old) = self.internal_state.borrow().first_id {
+                            prepended = data.iter().position(|m| m.id == *old);
+                        }
+                        first_id = data.first().map(|m| m.id.clone());
+
+                        // messages after the newest one seen last time are new,
+                        // unless that one is gone, e.g. a pending message that got confirmed
+                        last_id = data.last().map(|m| m.id.clone());
+                        if let Some(ref old) = self.internal_state.borrow().last_id {
+                            if let Some(idx) = data.iter().position(|m| m.id == *old) {
+                                // replies in a thread do not show up in the conversation
+                                arrived = data[idx + 1..].iter().filter(|m| in_timeline(m)).count();
+                            }
+                        }
+                        mine = data
+                            .iter()
+                            .rev()
+                            .find(|m| in_timeline(m))
+                            .is_some_and(|m| m.from == app.current_user());
+                    }
+                    None => placeholder = Some(String::from("messages is loading...")),
+                },
             }
         } else {
             title = String::from("No Session Selected");
         }
 
-        // let mut long_text = "abcdefg".chars().cycle().take(200).collect::<String>();
-        // long_text.push('\n');
-        // let long_text_len = long_text.len();
-        // messages.push(long_text);
-
         let mut internal = self.internal_state.borrow_mut();
+        if !same_session {
+            // a conversation opens at its newest message
+            internal.session = session;
+            internal.follow = true;
+            internal.unseen = 0;
+            internal.horizontal_scroll = 0;
+            internal.stop_selecting();
+            internal.search = None;
+        } else if arrived > 0 && mine {
+            // what the user just sent is what they want to see
+            internal.follow = true;
+            internal.unseen = 0;
+        } else if !internal.follow {
+            internal.unseen += arrived;
+        }
+        internal.last_id = last_id;
         internal.title = title;
-        internal.messages = messages;
-        internal.vertical_scroll_state = internal
-            .vertical_scroll_state
-            .content_length(internal.messages.len());
-        // internal.horizontal_scroll_state = internal
-        //     .horizontal_scroll_state
-        //     .content_length(long_text_len);
+        internal.placeholder = placeholder;
+        internal.first_id = first_id;
+        internal.prepended = prepended;
+
+        let data = app.messages_model.get_model_data();
+        let messages = data.data().map(|v| v.as_slice()).unwrap_or_default();
+        internal.update_search(messages, app.messages_model.get_search());
+
+        // something went wrong since the last time, the user hears about it once
+        let (failures, failed) = app.messages_model.get_last_failure();
+        if failures != internal.failures_told {
+            internal.failures_told = failures;
+            let reason = failed.and_then(|id| app.messages_model.get_failure(id));
+            let status = failed
+                .and_then(|id| messages.iter().find(|m| m.id == *id))
+                .map(|m| m.status);
+            let what = match status {
+                Some(DeliveryStatus::Failed) => "could not send the message",
+                // an edit, a delete or a reaction taken back
+                Some(_) => "the change was undone",
+                // it happened in another conversation
+                None => "something went wrong",
+            };
+            if let Some(reason) = reason {
+                internal.notify(format!("{}: {}", what, reason));
+            }
+        }
+        if !internal.store_error_told {
+            if let Some(err) = app.messages_model.get_store_error() {
+                internal.store_error_told = true;
+                internal.notify(format!("history is no longer saved: {}", err));
+            }
+        }
     }
 
     fn get_ui_paragraph<'a>(&self, app: &App, area: Rect) -> Paragraph<'a> {
@@ -134,51 +481,477 @@ impl RightSpace {
             _ => Style::default(),
         };
 
-        let create_block = |title| {
-            Block::default()
+        // a refresh in flight or a failed one is shown around the old content, not instead of it
+        let data = app.messages_model.get_model_data();
+        let (unseen, selecting, group, notice, search) = {
+            let mut internal = self.internal_state.borrow_mut();
+            if internal
+                .notice
+                .as_ref()
+                .is_some_and(|(_, at)| at.elapsed() > NOTICE_TIMEOUT)
+            {
+                internal.notice = None;
+            }
+            let notice = internal.notice.as_ref().map(|(text, _)| text.clone());
+            let group = matches!(internal.session, Some(ChatSession::Group(_)));
+            let search = internal.search.as_ref().map(SearchBar::title);
+            (internal.unseen, internal.selecting, group, notice, search)
+        };
+        let create_block = |title: String| {
+            let mut block = Block::default()
                 .borders(Borders::ALL)
                 .border_style(bdr_stl)
                 .gray()
                 .title(Span::styled(
                     title,
                     Style::default().add_modifier(Modifier::BOLD),
-                ))
+                ));
+            if data.is_loading() {
+                block = block.title(
+                    Title::from(Span::styled(self.meta_data.spinner(), Style::new().dim()))
+                        .alignment(Alignment::Right),
+                );
+            }
+            // titles running past the border are cut short, each one starts after the one before
+            let mut room = area.width.saturating_sub(2);
+            let mut fit = |text: &str| {
+                let text = ellipsize(text, room);
+                room = room.saturating_sub(text.width() as u16 + 1);
+                text
+            };
+            if let Some(err) = data.error() {
+                block = block.title(
+                    Title::from(Span::styled(
+                        fit(&format!(" ⚠ {} · r to retry ", err)),
+                        Style::new().red(),
+                    ))
+                    .position(Position::Bottom),
+                );
+            }
+            if let Some(ref notice) = notice {
+                block = block.title(
+                    Title::from(Span::styled(
+                        fit(&format!(" {} ", notice)),
+                        Style::new().fg(Color::Yellow),
+                    ))
+                    .position(Position::Bottom),
+                );
+            } else if let Some(ref search) = search {
+                block = block.title(
+                    Title::from(Span::styled(fit(search), Style::new().fg(Color::Yellow)))
+                        .position(Position::Bottom),
+                );
+            } else if selecting {
+                let hint = if group {
+                    GROUP_SELECTION_HINT
+                } else {
+                    SELECTION_HINT
+                };
+                block = block.title(
+                    Title::from(Span::styled(fit(hint), Style::new().dim()))
+                        .position(Position::Bottom),
+                );
+            }
+            if unseen > 0 {
+                let plural = if unseen == 1 { "" } else { "s" };
+                block = block.title(
+                    Title::from(Span::styled(
+                        format!(" {} new message{} ↓ G ", unseen, plural),
+                        Style::new().fg(Color::Black).bg(Color::Yellow),
+                    ))
+                    .position(Position::Bottom)
+                    .alignment(Alignment::Right),
+                );
+            }
+            block
         };
 
         let mut internal = self.internal_state.borrow_mut();
-
-        let mut text = Text::default();
         let max_width = area.width / 3 * 2;
-        let bbls = internal
-            .messages
+        let view_height = area.height.saturating_sub(2) as usize;
+
+        if let Some(ref placeholder) = internal.placeholder {
+            let mut bbl = MessageBubble::new(max_width, placeholder, "PIXAL")
+                .with_theme(self.bubble_theme.clone());
+            bbl.finish_painting();
+            return Paragraph::new(Text::from(bbl))
+                .gray()
+                .block(create_block(internal.title.clone()));
+        }
+
+        let mut top = Vec::new();
+        if data.data().is_some() {
+            if let Some(line) = self.get_scrollback_line(app) {
+                top.push(line);
+            }
+        }
+        let top_rows = top.len();
+
+        // every bubble is measured, that is cheap once they are laid out
+        let messages = data.data().map(|v| v.as_slice()).unwrap_or_default();
+        let me = app.current_user();
+        // the user's own messages go to the right, like in most chat apps
+        let side_of = |m: &Message| {
+            if m.from == me {
+                BubbleSide::Right
+            } else {
+                BubbleSide::Left
+            }
+        };
+        let loaded = messages
             .iter()
-            .map(|m| {
-                let mut bbl = MessageBubble::new(max_width, &m.1, &m.0);
-                bbl.finish_painting();
-                Text::from(bbl)
-            })
-            .collect::<Vec<_>>();
+            .map(|m| (&m.id, m))
+            .collect::<HashMap<_, _>>();
+        let replies = thread_replies(messages);
+        let flows = flow(messages);
+        let today = *internal
+            .today
+            .get_or_insert_with(|| Local::now().date_naive());
+        let found = internal.search.as_ref().and_then(|bar| {
+            let found = Found {
+                query: bar.query.clone(),
+                matcher: bar.matcher.clone()?,
+            };
+            Some((found, bar.hits.iter().cloned().collect::<HashSet<_>>()))
+        });
+        let decoration_of = |idx: usize, m: &Message| Decoration {
+            quote: quote_of(m, &loaded),
+            replies: replies.get(&m.id).copied().unwrap_or(0),
+            continued: flows[idx].continued,
+            found: found
+                .as_ref()
+                .filter(|(_, hits)| hits.contains(&m.id))
+                .map(|(found, _)| found.clone()),
+            ..Decoration::of(m, &me, today)
+        };
+        let mut layouts = self.layouts.borrow_mut();
+        layouts.set_width(max_width);
+        layouts.retain(messages);
+        let code_scroll = internal.horizontal_scroll as u16;
+        let mut tops = Vec::with_capacity(messages.len());
+        let mut heights = Vec::with_capacity(messages.len());
+        let mut total = top_rows;
+        for (idx, m) in messages.iter().enumerate() {
+            let divider = usize::from(flows[idx].divider.is_some());
+            let height = match in_timeline(m) {
+                true => {
+                    layouts
+                        .get(
+                            m,
+                            decoration_of(idx, m),
+                            side_of(m),
+                            &self.bubble_theme,
+                            code_scroll,
+                            false,
+                        )
+                        .lines
+                        .len()
+                        + divider
+                }
+                false => 0,
+            };
+            tops.push(total);
+            heights.push(height);
+            total += height;
+        }
 
-        for b in bbls {
-            text.extend(b);
+        // whatever was added above the viewport pushes the scroll position down by as much,
+        // unless the viewport is at the very top, where the new row is what the user waits for
+        let prepended = internal.prepended.take();
+        let shift = match prepended {
+            _ if internal.follow => false,
+            Some(0) => internal.vertical_scroll > 0,
+            Some(_) => true,
+            None => false,
+        };
+        if shift {
+            let added = tops.get(prepended.unwrap_or(0)).copied().unwrap_or(total);
+            internal.vertical_scroll =
+                (internal.vertical_scroll + added).saturating_sub(internal.top_rows);
+        }
+        internal.top_rows = top_rows;
+
+        // the viewport follows the newest message or stays where it was put,
+        // if it was scrolled back to the bottom it sticks there again
+        internal.total_rows = total;
+        internal.view_height = view_height;
+        // the original of a reply was not loaded when jumping to it, a page later it may be
+        if let Some(parent) = internal.jump_to.take() {
+            if !internal.jump_to_original(messages, &parent) {
+                let scrollback = app.messages_model.get_scrollback();
+                let gave_up = if scrollback.exhausted && !scrollback.loading {
+                    Some("the original message is gone")
+                } else if prepended.is_some_and(|added| added > 0) || scrollback.error.is_some() {
+                    Some("the original is further back, p to keep looking")
+                } else {
+                    None
+                };
+                if let Some(notice) = gave_up {
+                    internal.jump_to = None;
+                    internal.notify(notice);
+                }
+            }
+        }
+        let focus = internal.resolve_focus(messages);
+        if let Some(idx) = focus.filter(|_| internal.reveal_focus) {
+            internal.reveal_focus = false;
+            internal.reveal(tops[idx], tops[idx] + heights[idx]);
+        }
+        if internal.follow || internal.vertical_scroll >= internal.max_scroll() {
+            internal.scroll_to_bottom();
+        }
+
+        // only what is in sight gets painted
+        let scroll = internal.vertical_scroll;
+        let range = in_sight(&tops, &heights, scroll, view_height);
+        let mut text = Text::default();
+        let mut start = tops.get(range.start).copied().unwrap_or(total);
+        if scroll < top_rows || range.is_empty() {
+            text.lines.extend(top);
+            start = 0;
+        }
+        let mut code_overflow = 0;
+        internal.in_sight = range.clone();
+        for (idx, m) in messages
+            .iter()
+            .enumerate()
+            .take(range.end)
+            .skip(range.start)
+        {
+            if !in_timeline(m) {
+                continue;
+            }
+            if let Some(day) = flows[idx].divider {
+                text.lines.push(
+                    Line::styled(
+                        format!("── {} ──", day_label(day, today)),
+                        Style::new().dim(),
+                    )
+                    .alignment(Alignment::Center),
+                );
+            }
+            // the focused bubble is drawn differently, it is not worth a place in the cache
+            if focus == Some(idx) {
+                let laid = lay_out(
+                    m,
+                    decoration_of(idx, m),
+                    side_of(m),
+                    &self.bubble_theme,
+                    max_width,
+                    code_scroll,
+                    true,
+                );
+                code_overflow = code_overflow.max(laid.code_overflow as usize);
+                text.lines.extend(laid.lines);
+                continue;
+            }
+            let laid = layouts.get(
+                m,
+                decoration_of(idx, m),
+                side_of(m),
+                &self.bubble_theme,
+                code_scroll,
+                true,
+            );
+            code_overflow = code_overflow.max(laid.code_overflow as usize);
+            text.lines.extend(laid.lines.iter().cloned());
         }
 
         internal.vertical_scroll_state = internal
             .vertical_scroll_state
-            .content_length(text.lines.len());
+            .content_length(total)
+            .position(scroll);
+        // code blocks scroll sideways, not the whole conversation
+        internal.horizontal_scroll = internal.horizontal_scroll.min(code_overflow);
+        internal.horizontal_scroll_state = internal
+            .horizontal_scroll_state
+            .content_length(code_overflow)
+            .position(internal.horizontal_scroll);
 
         let paragraph = Paragraph::new(text)
             .gray()
             .block(create_block(internal.title.clone()))
             .alignment(Alignment::Left)
-            .scroll((
-                internal.vertical_scroll as u16,
-                internal.horizontal_scroll as u16,
-            ));
+            .scroll(((scroll - start) as u16, 0));
 
         paragraph
     }
 
+    // scrolling around, and what else works the same with or without a cursor
+    fn handle_browsing_key(&mut self, event: TerminalEvent) -> TerminalEventResult {
+        let mut internal = self.internal_state.borrow_mut();
+        let mut ret = TerminalEventResult::Handled(Action::Nop);
+        match event {
+            TerminalEvent::Key(key) => {
+                let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+                match key.code {
+                    KeyCode::Char('d') if ctrl => {
+                        let rows = internal.half_page();
+                        internal.scroll_down(rows);
+                    }
+                    KeyCode::Char('u') if ctrl => {
+                        let rows = internal.half_page();
+                        ret = scroll_up(&mut internal, rows);
+                    }
+                    KeyCode::Char('j') | KeyCode::Down => internal.scroll_down(1),
+                    KeyCode::Char('k') | KeyCode::Up => ret = scroll_up(&mut internal, 1),
+                    KeyCode::PageDown => {
+                        let rows = internal.view_height.max(1);
+                        internal.scroll_down(rows);
+                    }
+                    KeyCode::PageUp => {
+                        let rows = internal.view_height.max(1);
+                        ret = scroll_up(&mut internal, rows);
+                    }
+                    KeyCode::Char('g') | KeyCode::Home => {
+                        ret = scroll_up(&mut internal, usize::MAX)
+                    }
+                    KeyCode::Char('G') | KeyCode::End => internal.scroll_to_bottom(),
+                    KeyCode::Char('h') => {
+                        internal.horizontal_scroll = internal.horizontal_scroll.saturating_sub(1);
+                        internal.horizontal_scroll_state = internal
+                            .horizontal_scroll_state
+                            .position(internal.horizontal_scroll);
+                    }
+                    KeyCode::Char('l') => {
+                        internal.horizontal_scroll = internal.horizontal_scroll.saturating_add(1);
+                        internal.horizontal_scroll_state = internal
+                            .horizontal_scroll_state
+                            .position(internal.horizontal_scroll);
+                    }
+                    KeyCode::Char('r') => {
+                        ret = TerminalEventResult::Handled(Action::MessagesModel(
+                            MessagesModelAction::Reload,
+                        ));
+                    }
+                    KeyCode::Char('i') => {
+                        ret = TerminalEventResult::Handled(Action::StateModel(
+                            StateModelAction::SetActive(StateModel::Composer),
+                        ));
+                    }
+                    _ => ret = TerminalEventResult::NotHandled(event),
+                }
+            }
+            _ => ret = TerminalEventResult::NotHandled(event),
+        }
+
+        ret
+    }
+
+    // everything there is to know about the focused message, in a box over the conversation
+    fn get_ui_details<'a>(&self, app: &App, area: Rect) -> Option<(Rect, Paragraph<'a>)> {
+        let internal = self.internal_state.borrow();
+        if !internal.details {
+            return None;
+        }
+        let data = app.messages_model.get_model_data();
+        let message = internal.focused(data.data()?)?;
+
+        let field = |name: &str, value: String| {
+            Line::from(vec![
+                Span::styled(format!("{:>8} ", name), Style::new().dim()),
+                Span::raw(value),
+            ])
+        };
+        let time = |at: &chrono::DateTime<chrono::Utc>| {
+            at.with_timezone(&chrono::Local)
+                .format("%Y-%m-%d %H:%M:%S")
+                .to_string()
+        };
+        let mut lines = vec![
+            field("id", message.id.0.clone()),
+            field("from", message.from.0.clone()),
+            field(
+                "to",
+                match message.to {
+                    Receiver::Individual(ref usr) => usr.0.clone(),
+                    Receiver::Group(ref grp) => format!("group {}", grp.0),
+                },
+            ),
+            field("sent", time(&message.sent_at)),
+            field("status", format!("{:?}", message.status)),
+        ];
+        if let Some(reason) = app.messages_model.get_failure(&message.id) {
+            lines.push(field("failed", reason.clone()));
+        }
+        if let Some(ref parent) = message.reply_to {
+            lines.push(field("reply to", parent.0.clone()));
+        }
+        if let Some(ref edited_at) = message.edited_at {
+            lines.push(field("edited", time(edited_at)));
+        }
+        // earlier versions, the latest first
+        let excerpt_width = area.width.saturating_sub(4).min(60).saturating_sub(11);
+        for content in message.history.iter().rev() {
+            lines.push(field(
+                "was",
+                ellipsize(&markdown::excerpt(content), excerpt_width),
+            ));
+        }
+        if message.deleted {
+            lines.push(field("deleted", String::from("yes")));
+        }
+        for reaction in message.reactions.iter() {
+            let users = reaction
+                .users
+                .iter()
+                .map(|usr| usr.0.as_str())
+                .collect::<Vec<_>>();
+            lines.push(field(&reaction.emoji, users.join(", ")));
+        }
+        lines.push(field(
+            "length",
+            format!("{} chars", message.content.chars().count()),
+        ));
+
+        let width = area.width.saturating_sub(4).min(60);
+        let height = (lines.len() as u16 + 2).min(area.height);
+        let popup = Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + (area.height - height) / 2,
+            width,
+            height,
+        };
+        let paragraph = Paragraph::new(lines).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::new().fg(Color::Green))
+                .title(" details · esc to close "),
+        );
+        Some((popup, paragraph))
+    }
+
+    // the emojis to react with in a row over the conversation, for the focused message
+    fn get_ui_picker<'a>(&self, app: &App, area: Rect) -> Option<(Rect, Paragraph<'a>)> {
+        let internal = self.internal_state.borrow();
+        let picker = internal.picker.as_ref()?;
+        let data = app.messages_model.get_model_data();
+        let message = internal.focused(data.data()?)?;
+        let me = app.current_user();
+        Some(picker.get_ui(message, &me, &self.bubble_theme, area))
+    }
+
+    // the row above the first message telling how far back the history goes
+    fn get_scrollback_line<'a>(&self, app: &App) -> Option<Line<'a>> {
+        let scrollback = app.messages_model.get_scrollback();
+        let line = if scrollback.loading {
+            Line::styled(
+                format!("{} loading older messages", self.meta_data.spinner()),
+                Style::new().dim(),
+            )
+        } else if let Some(ref err) = scrollback.error {
+            Line::styled(
+                format!("⚠ older messages failed to load: {} · k to retry", err),
+                Style::new().red(),
+            )
+        } else if scrollback.exhausted {
+            Line::styled("beginning of the conversation", Style::new().dim())
+        } else {
+            return None;
+        };
+        Some(line.alignment(Alignment::Center))
+    }
+
     fn get_ui_horizontal_scrollbar<'a>(&self, _app: &App) -> Scrollbar<'a> {
         let ret = Scrollbar::default()
             .orientation(ScrollbarOrientation::HorizontalBottom)
@@ -214,6 +987,14 @@ impl UiEntity for RightSpace {
             area,
             &mut self.internal_state.borrow_mut().horizontal_scroll_state,
         );
+        if let Some((popup, details)) = self.get_ui_details(app, area) {
+            frame.render_widget(Clear, popup);
+            frame.render_widget(details, popup);
+        }
+        if let Some((popup, picker)) = self.get_ui_picker(app, area) {
+            frame.render_widget(Clear, popup);
+            frame.render_widget(picker, popup);
+        }
     }
 
     fn make_blueprints<'a, 'b>(
@@ -226,40 +1007,585 @@ impl UiEntity for RightSpace {
     {
     }
 
-    fn handle_terminal_event(&mut self, event: TerminalEvent, _app: &App) -> TerminalEventResult {
+    fn handle_terminal_event(&mut self, event: TerminalEvent, app: &App) -> TerminalEventResult {
         let mut internal = self.internal_state.borrow_mut();
         let mut ret = TerminalEventResult::Handled(Action::Nop);
+        // the search keys work with or without a cursor, but not while the picker is open
+        let searching = |internal: &InternalState, key: &KeyEvent| {
+            internal.picker.is_none()
+                && !key
+                    .modifiers
+                    .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+        };
         match event {
-            TerminalEvent::Key(key) => match key.code {
-                KeyCode::Char('j') => {
-                    internal.vertical_scroll = internal.vertical_scroll.saturating_add(1);
-                    internal.vertical_scroll_state = internal
-                        .vertical_scroll_state
-                        .position(internal.vertical_scroll);
-                }
-                KeyCode::Char('k') => {
-                    internal.vertical_scroll = internal.vertical_scroll.saturating_sub(1);
-                    internal.vertical_scroll_state = internal
-                        .vertical_scroll_state
-                        .position(internal.vertical_scroll);
+            TerminalEvent::Tick => internal.tick(),
+            TerminalEvent::Key(key) if internal.search.as_ref().is_some_and(|bar| bar.typing) => {
+                ret = handle_search_key(&mut internal, key);
+            }
+            TerminalEvent::Key(key)
+                if key.code == KeyCode::Char('/') && searching(&internal, &key) =>
+            {
+                internal.details = false;
+                internal.open_search();
+            }
+            TerminalEvent::Key(key)
+                if matches!(key.code, KeyCode::Char('n' | 'N'))
+                    && internal.search.is_some()
+                    && searching(&internal, &key) =>
+            {
+                let data = app.messages_model.get_model_data();
+                let messages = data.data().map(|v| v.as_slice()).unwrap_or_default();
+                ret = internal.next_hit(messages, key.code == KeyCode::Char('N'));
+            }
+            TerminalEvent::Key(key)
+                if key.code == KeyCode::Esc
+                    && internal.search.is_some()
+                    && !internal.details
+                    && searching(&internal, &key) =>
+            {
+                internal.search = None;
+                ret = TerminalEventResult::Handled(Action::MessagesModel(
+                    MessagesModelAction::Search(None),
+                ));
+            }
+            TerminalEvent::Key(key)
+                if internal.selecting
+                    && !key
+                        .modifiers
+                        .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+            {
+                let data = app.messages_model.get_model_data();
+                let messages = data.data().map(|v| v.as_slice()).unwrap_or_default();
+                if internal.picker.is_some() {
+                    return handle_picker_key(&mut internal, key.code, messages);
                 }
-                KeyCode::Char('h') => {
-                    internal.horizontal_scroll = internal.horizontal_scroll.saturating_sub(1);
-                    internal.horizontal_scroll_state = internal
-                        .horizontal_scroll_state
-                        .position(internal.horizontal_scroll);
+                let me = app.current_user();
+                match handle_selection_key(&mut internal, key.code, messages, &me) {
+                    Some(result) => ret = result,
+                    None => {
+                        drop(internal);
+                        return self.handle_browsing_key(event);
+                    }
                 }
-                KeyCode::Char('l') => {
-                    internal.horizontal_scroll = internal.horizontal_scroll.saturating_add(1);
-                    internal.horizontal_scroll_state = internal
-                        .horizontal_scroll_state
-                        .position(internal.horizontal_scroll);
+            }
+            TerminalEvent::Key(KeyEvent {
+                code: KeyCode::Char('v'),
+                ..
+            }) => {
+                let data = app.messages_model.get_model_data();
+                let messages = data.data().map(|v| v.as_slice()).unwrap_or_default();
+                internal.start_selecting(messages);
+            }
+            _ => {
+                drop(internal);
+                return self.handle_browsing_key(event);
+            }
+        }
+
+        ret
+    }
+}
+
+// the keys that act on the focused message, none for the keys that do not
+fn handle_selection_key(
+    internal: &mut InternalState,
+    code: KeyCode,
+    messages: &[Message],
+    me: &UsrID,
+) -> Option<TerminalEventResult> {
+    // messages may have come in since the last draw, the cursor is where its message is now
+    let focused = internal
+        .resolve_focus(messages)
+        .and_then(|idx| messages.get(idx));
+    // deleting has to be confirmed by the very next key
+    let deleting = internal.deleting.take();
+    match code {
+        KeyCode::Esc if internal.details => internal.details = false,
+        KeyCode::Esc | KeyCode::Char('v') => internal.stop_selecting(),
+        // the cursor skips the replies of threads, they are not in the conversation
+        KeyCode::Char('j') | KeyCode::Down => {
+            if let Some(idx) = timeline_from(messages, internal.focus_idx + 1, true) {
+                internal.focus_on(messages, idx);
+            }
+        }
+        KeyCode::Char('k') | KeyCode::Up => {
+            match internal
+                .focus_idx
+                .checked_sub(1)
+                .and_then(|idx| timeline_from(messages, idx, false))
+            {
+                Some(idx) => internal.focus_on(messages, idx),
+                // going up past the first message asks for the page before it
+                None => {
+                    return Some(TerminalEventResult::Handled(Action::MessagesModel(
+                        MessagesModelAction::FetchOlder,
+                    )))
                 }
-                _ => ret = TerminalEventResult::NotHandled(event),
+            }
+        }
+        KeyCode::Char('g') | KeyCode::Home => {
+            if let Some(idx) = timeline_from(messages, 0, true) {
+                internal.focus_on(messages, idx);
+            }
+        }
+        KeyCode::Char('G') | KeyCode::End => {
+            if let Some(idx) = timeline_from(messages, messages.len().saturating_sub(1), false) {
+                internal.focus_on(messages, idx);
+            }
+        }
+        KeyCode::Enter => internal.details = !internal.details,
+        // the raw text is copied, markup and all
+        KeyCode::Char('y') => {
+            let Some(message) = focused else {
+                return Some(TerminalEventResult::Handled(Action::Nop));
+            };
+            match Tio::copy_to_clipboard(&message.content) {
+                Ok(()) => internal.notify("copied to clipboard"),
+                Err(err) => internal.notify(format!("copy failed: {}", err)),
+            }
+        }
+        // only a message the backend knows can be answered
+        KeyCode::Char('r') => {
+            let Some(message) = focused else {
+                return Some(TerminalEventResult::Handled(Action::Nop));
+            };
+            if matches!(
+                message.status,
+                DeliveryStatus::Sending | DeliveryStatus::Failed
+            ) {
+                internal.notify("wait until the message is sent to reply to it");
+                return Some(TerminalEventResult::Handled(Action::Nop));
+            }
+            let parent = Some(message.id.clone());
+            internal.stop_selecting();
+            return Some(TerminalEventResult::Handled(Action::MultiAction(vec![
+                Action::MessagesModel(MessagesModelAction::ReplyTo(parent)),
+                Action::StateModel(StateModelAction::SetActive(StateModel::Composer)),
+            ])));
+        }
+        // a thread can only hang off a message the backend knows
+        KeyCode::Char('t') => {
+            let Some(message) = focused else {
+                return Some(TerminalEventResult::Handled(Action::Nop));
+            };
+            if !matches!(internal.session, Some(ChatSession::Group(_))) {
+                internal.notify("only group messages have threads");
+                return Some(TerminalEventResult::Handled(Action::Nop));
+            }
+            if matches!(
+                message.status,
+                DeliveryStatus::Sending | DeliveryStatus::Failed
+            ) {
+                internal.notify("wait until the message is sent to start a thread");
+                return Some(TerminalEventResult::Handled(Action::Nop));
+            }
+            let root = message.id.clone();
+            internal.stop_selecting();
+            return Some(TerminalEventResult::Handled(Action::MultiAction(vec![
+                Action::MessagesModel(MessagesModelAction::OpenThread(root)),
+                Action::StateModel(StateModelAction::SetActive(StateModel::Thread)),
+            ])));
+        }
+        KeyCode::Char('p') => {
+            let Some(parent) = focused.and_then(|m| m.reply_to.clone()) else {
+                internal.notify("this message is not a reply");
+                return Some(TerminalEventResult::Handled(Action::Nop));
+            };
+            if !internal.jump_to_original(messages, &parent) {
+                internal.notify("loading older messages to find the original");
+                return Some(TerminalEventResult::Handled(Action::MessagesModel(
+                    MessagesModelAction::FetchOlder,
+                )));
+            }
+        }
+        KeyCode::Char('+') => {
+            let Some(message) = focused else {
+                return Some(TerminalEventResult::Handled(Action::Nop));
+            };
+            if matches!(
+                message.status,
+                DeliveryStatus::Sending | DeliveryStatus::Failed
+            ) {
+                internal.notify("wait until the message is sent to react to it");
+                return Some(TerminalEventResult::Handled(Action::Nop));
+            }
+            internal.details = false;
+            internal.picker = Some(ReactionPicker::default());
+        }
+        KeyCode::Char('e') => {
+            let Some(message) = focused else {
+                return Some(TerminalEventResult::Handled(Action::Nop));
+            };
+            if let Some(refusal) = refuse_change(message, me, "edited") {
+                internal.notify(refusal);
+                return Some(TerminalEventResult::Handled(Action::Nop));
+            }
+            let id = Some(message.id.clone());
+            internal.stop_selecting();
+            return Some(TerminalEventResult::Handled(Action::MultiAction(vec![
+                Action::MessagesModel(MessagesModelAction::Editing(id)),
+                Action::StateModel(StateModelAction::SetActive(StateModel::Composer)),
+            ])));
+        }
+        KeyCode::Char('d') => {
+            let Some(message) = focused else {
+                return Some(TerminalEventResult::Handled(Action::Nop));
+            };
+            if let Some(refusal) = refuse_change(message, me, "deleted") {
+                internal.notify(refusal);
+                return Some(TerminalEventResult::Handled(Action::Nop));
+            }
+            if deleting.as_ref() != Some(&message.id) {
+                internal.deleting = Some(message.id.clone());
+                internal.notify("d again to delete this message");
+                return Some(TerminalEventResult::Handled(Action::Nop));
+            }
+            internal.notice = None;
+            return Some(TerminalEventResult::Handled(Action::MessagesModel(
+                MessagesModelAction::Delete(message.id.clone()),
+            )));
+        }
+        KeyCode::Char('s') => {
+            let Some(message) = focused else {
+                return Some(TerminalEventResult::Handled(Action::Nop));
+            };
+            if message.status != DeliveryStatus::Failed {
+                internal.notify("only a message that could not be sent is sent again");
+                return Some(TerminalEventResult::Handled(Action::Nop));
+            }
+            // it goes to the end of the conversation, where the view follows it
+            internal.stop_selecting();
+            return Some(TerminalEventResult::Handled(Action::MessagesModel(
+                MessagesModelAction::Resend(message.id.clone()),
+            )));
+        }
+        _ => return None,
+    }
+    Some(TerminalEventResult::Handled(Action::Nop))
+}
+
+// why the user can not edit or delete the message, none if they can
+fn refuse_change(message: &Message, me: &UsrID, change: &str) -> Option<String> {
+    if message.from != *me {
+        Some(format!("only your own messages can be {}", change))
+    } else if message.deleted {
+        Some(String::from("this message is deleted already"))
+    } else if matches!(
+        message.status,
+        DeliveryStatus::Sending | DeliveryStatus::Failed
+    ) {
+        Some(format!(
+            "the message is not sent yet, it can not be {}",
+            change
+        ))
+    } else {
+        None
+    }
+}
+
+// the keys of the search bar while the query is typed, it takes them all
+fn handle_search_key(internal: &mut InternalState, key: KeyEvent) -> TerminalEventResult {
+    let Some(bar) = internal.search.as_mut() else {
+        return TerminalEventResult::Handled(Action::Nop);
+    };
+    let search = match bar.type_key(key) {
+        Typed::Editing => return TerminalEventResult::Handled(Action::Nop),
+        Typed::Closed => {
+            internal.search = None;
+            None
+        }
+        Typed::Find(query) => Some(query),
+        Typed::Invalid(err) => {
+            internal.notify(format!("not a valid regex: {}", err));
+            return TerminalEventResult::Handled(Action::Nop);
+        }
+    };
+    TerminalEventResult::Handled(Action::MessagesModel(MessagesModelAction::Search(search)))
+}
+
+// the keys of the reaction picker, it takes them all while it is open
+fn handle_picker_key(
+    internal: &mut InternalState,
+    code: KeyCode,
+    messages: &[Message],
+) -> TerminalEventResult {
+    let Some(picker) = internal.picker.as_mut() else {
+        return TerminalEventResult::Handled(Action::Nop);
+    };
+    let emoji = match picker.handle_key(code) {
+        Picking::Browsing => return TerminalEventResult::Handled(Action::Nop),
+        Picking::Closed => {
+            internal.picker = None;
+            return TerminalEventResult::Handled(Action::Nop);
+        }
+        Picking::Picked(emoji) => emoji,
+    };
+    let Some(message) = internal.focused(messages) else {
+        return TerminalEventResult::Handled(Action::Nop);
+    };
+    let id = message.id.clone();
+    internal.picker = None;
+    TerminalEventResult::Handled(Action::MessagesModel(MessagesModelAction::React(
+        id,
+        emoji.to_string(),
+    )))
+}
+
+// scrolling up past the top asks for the page before it
+fn scroll_up(internal: &mut InternalState, rows: usize) -> TerminalEventResult {
+    if internal.scroll_up(rows) {
+        TerminalEventResult::Handled(Action::Nop)
+    } else {
+        TerminalEventResult::Handled(Action::MessagesModel(MessagesModelAction::FetchOlder))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{fixtures::message, SearchQuery};
+
+    #[test]
+    fn test_scrolling_sticks_to_the_bottom() {
+        let mut state = InternalState {
+            total_rows: 100,
+            view_height: 20,
+            ..Default::default()
+        };
+        state.scroll_to_bottom();
+        assert_eq!(state.vertical_scroll, 80);
+
+        assert!(state.scroll_up(state.half_page()));
+        assert_eq!(state.vertical_scroll, 70);
+        assert!(!state.follow);
+        state.unseen = 3;
+
+        state.scroll_down(100);
+        assert_eq!(state.vertical_scroll, 80);
+        assert!(state.follow);
+        assert_eq!(state.unseen, 0);
+
+        assert!(state.scroll_up(usize::MAX));
+        assert_eq!(state.vertical_scroll, 0);
+        // already at the top, older messages are asked for instead
+        assert!(!state.scroll_up(1));
+    }
+
+    #[test]
+    fn test_focus_survives_new_and_removed_messages() {
+        let message = |id: &str| message(id, "kevin", "");
+        let mut messages = vec![message("b"), message("c"), message("d")];
+        let mut state = InternalState {
+            in_sight: 0..2,
+            ..Default::default()
+        };
+        state.start_selecting(&messages);
+        assert_eq!(state.resolve_focus(&messages), Some(1));
+
+        // older messages above and newer ones below do not move the cursor off "c"
+        messages.insert(0, message("a"));
+        messages.push(message("e"));
+        assert_eq!(state.resolve_focus(&messages), Some(2));
+        assert_eq!(state.focus, Some(MsgID("c".to_string())));
+
+        // once "c" is gone the cursor lands on the message now in its place
+        messages.remove(2);
+        assert_eq!(state.resolve_focus(&messages), Some(2));
+        assert_eq!(state.focus, Some(MsgID("d".to_string())));
+    }
+
+    #[test]
+    fn test_thread_replies_stay_out_of_the_timeline() {
+        let message = |id: &str, thread: Option<&str>| {
+            message(id, "kevin", "").with_thread(thread.map(|t| MsgID(t.to_string())))
+        };
+        let messages = vec![
+            message("a", None),
+            message("b", Some("a")),
+            message("c", Some("a")),
+            message("d", None),
+            message("e", Some("d")),
+        ];
+        let replies = thread_replies(&messages);
+        assert_eq!(replies.get(&MsgID("a".to_string())), Some(&2));
+        assert_eq!(replies.get(&MsgID("d".to_string())), Some(&1));
+
+        assert_eq!(timeline_from(&messages, 1, true), Some(3));
+        assert_eq!(timeline_from(&messages, 2, false), Some(0));
+        assert_eq!(timeline_from(&messages, 4, true), None);
+        assert_eq!(timeline_from(&messages, 9, false), Some(3));
+
+        // the cursor never lands on a reply, not even when it started out on one
+        let mut state = InternalState {
+            selecting: true,
+            focus: Some(MsgID("e".to_string())),
+            ..Default::default()
+        };
+        assert_eq!(state.resolve_focus(&messages), Some(3));
+    }
+
+    #[test]
+    fn test_days_are_divided_and_authors_grouped() {
+        let now = Local::now().date_naive().and_hms_opt(12, 0, 0).unwrap();
+        let message = |from: &str, days_ago: i64, minutes: i64| {
+            let id = format!("{}-{}-{}", from, days_ago, minutes);
+            let mut message = message(&id, from, "");
+            let at = now - chrono::Duration::days(days_ago) + chrono::Duration::minutes(minutes);
+            message.sent_at = at.and_local_timezone(Local).unwrap().into();
+            message
+        };
+        let messages = vec![
+            message("kevin", 1, 0),
+            message("kevin", 1, 3),
+            message("alice", 0, 0),
+            message("alice", 0, 2),
+            message("alice", 0, 20),
+        ];
+        let flows = flow(&messages);
+        let today = now.date();
+        let yesterday = today.pred_opt().unwrap();
+        let divided = flows.iter().map(|f| f.divider).collect::<Vec<_>>();
+        assert_eq!(divided, [Some(yesterday), None, Some(today), None, None]);
+        let continued = flows.iter().map(|f| f.continued).collect::<Vec<_>>();
+        assert_eq!(continued, [false, true, false, true, false]);
+
+        assert_eq!(day_label(today, today), "Today");
+        assert_eq!(day_label(yesterday, today), "Yesterday");
+        let day = NaiveDate::from_ymd_opt(2020, 10, 12).unwrap();
+        assert_eq!(day_label(day, today), "Mon 12 Oct 2020");
+    }
+
+    #[test]
+    fn test_search_walks_through_the_hits() {
+        let message = |id: &str, content: &str, minutes_ago: i64| {
+            let mut message = message(id, "kevin", content);
+            message.sent_at -= chrono::Duration::minutes(minutes_ago);
+            message
+        };
+        let messages = vec![
+            message("b", "Lunch?", 4),
+            message("c", "no", 3),
+            message("d", "lunch it is", 2),
+        ];
+        // the backend found one further back as well
+        let search = Search {
+            query: SearchQuery {
+                text: String::from("lunch"),
+                ..Default::default()
             },
-            _ => ret = TerminalEventResult::NotHandled(event),
+            hits: RemoteData::Success(vec![message("a", "lunch tomorrow?", 9)]),
+        };
+        let mut state = InternalState::default();
+        state.open_search();
+        for c in "lunch".chars() {
+            handle_search_key(&mut state, KeyEvent::from(KeyCode::Char(c)));
         }
+        let result = handle_search_key(&mut state, KeyEvent::from(KeyCode::Enter));
+        assert!(matches!(
+            result,
+            TerminalEventResult::Handled(Action::MessagesModel(MessagesModelAction::Search(Some(
+                _
+            ))))
+        ));
 
-        ret
+        // the cursor lands on the newest hit
+        state.update_search(&messages, Some(&search));
+        let ids = |state: &InternalState| {
+            let bar = state.search.as_ref().unwrap();
+            bar.hits.iter().map(|id| id.0.as_str()).collect::<String>()
+        };
+        assert_eq!(ids(&state), "abd");
+        assert_eq!(state.focus, Some(MsgID("d".to_string())));
+
+        state.next_hit(&messages, false);
+        assert_eq!(state.focus, Some(MsgID("b".to_string())));
+        // the oldest one is not loaded, older pages are asked for until it is
+        let result = state.next_hit(&messages, false);
+        assert!(matches!(
+            result,
+            TerminalEventResult::Handled(Action::MessagesModel(MessagesModelAction::FetchOlder))
+        ));
+        assert_eq!(state.jump_to, Some(MsgID("a".to_string())));
+        state.next_hit(&messages, true);
+        assert_eq!(state.focus, Some(MsgID("b".to_string())));
+        assert_eq!(state.jump_to, None);
+    }
+
+    #[test]
+    fn test_picker_reacts_to_the_focused_message() {
+        let messages = vec![message("a", "kevin", "")];
+        let mut state = InternalState {
+            picker: Some(ReactionPicker::default()),
+            focus: Some(MsgID("a".to_string())),
+            ..Default::default()
+        };
+        handle_picker_key(&mut state, KeyCode::Char('l'), &messages);
+        handle_picker_key(&mut state, KeyCode::Char('l'), &messages);
+        assert_eq!(state.picker.as_ref().map(|p| p.selected), Some(2));
+
+        let picked = |result| match result {
+            TerminalEventResult::Handled(Action::MessagesModel(MessagesModelAction::React(
+                id,
+                emoji,
+            ))) => Some((id.0, emoji)),
+            _ => None,
+        };
+        let result = handle_picker_key(&mut state, KeyCode::Enter, &messages);
+        assert_eq!(picked(result), Some(("a".to_string(), "😄".to_string())));
+        assert!(state.picker.is_none());
+
+        state.picker = Some(ReactionPicker::default());
+        assert!(picked(handle_picker_key(&mut state, KeyCode::Char('9'), &messages)).is_none());
+        let result = handle_picker_key(&mut state, KeyCode::Char('4'), &messages);
+        assert_eq!(picked(result), Some(("a".to_string(), "🎉".to_string())));
+    }
+
+    #[tokio::test]
+    async fn test_keys_act_on_the_focused_message_after_a_page_came_in() {
+        use crate::backend::mock::MockBackend;
+        use crate::models::store::LocalStore;
+        use ratatui::{buffer::Buffer, widgets::Widget};
+        use std::sync::Arc;
+
+        let store = Rc::new(RefCell::new(LocalStore::in_memory()));
+        let mut app = App::new(Arc::new(MockBackend::new()), store).unwrap();
+        let session = app.messages_model.bind.clone().unwrap();
+        let sent = |id: &str| message(id, "alice", id);
+        let page = vec![sent("m-b"), sent("m-c"), sent("m-d")];
+        app.messages_model
+            .handle_action(MessagesModelAction::SetMessages(session.clone(), page));
+
+        // the cursor was put on "m-c" by the last draw
+        let mut viewer = RightSpace::default();
+        viewer.internal_state.borrow_mut().selecting = true;
+        viewer.internal_state.borrow_mut().focus = Some(MsgID("m-c".to_string()));
+        viewer.internal_state.borrow_mut().focus_idx = 1;
+
+        // an older page comes in before the next draw
+        let older = vec![sent("m-0"), sent("m-a")];
+        app.messages_model
+            .handle_action(MessagesModelAction::PrependMessages(session, older));
+
+        let press = |viewer: &mut RightSpace, code| {
+            viewer.handle_terminal_event(TerminalEvent::Key(KeyEvent::from(code)), &app)
+        };
+        press(&mut viewer, KeyCode::Enter);
+        let area = Rect::new(0, 0, 60, 20);
+        let (popup, details) = viewer.get_ui_details(&app, area).unwrap();
+        let mut buf = Buffer::empty(area);
+        details.render(popup, &mut buf);
+        let shown = buf.content.iter().map(|c| c.symbol()).collect::<String>();
+        assert!(shown.contains("id m-c"), "{}", shown);
+
+        let replied = match press(&mut viewer, KeyCode::Char('r')) {
+            TerminalEventResult::Handled(Action::MultiAction(actions)) => {
+                actions.into_iter().find_map(|action| match action {
+                    Action::MessagesModel(MessagesModelAction::ReplyTo(parent)) => parent,
+                    _ => None,
+                })
+            }
+            _ => None,
+        };
+        assert_eq!(replied, Some(MsgID("m-c".to_string())));
     }
 }
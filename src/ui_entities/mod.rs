@@ -9,24 +9,33 @@ use crate::app::App;
 use crate::tio::TerminalEvent;
 
 pub mod blueprints;
+pub mod bubble_layouts;
 pub mod chat_item;
 pub mod chat_sidebar;
 pub mod fps_hint;
 pub mod input_field;
 pub mod keypress_hint;
 pub mod message_viewer;
+pub mod reaction_picker;
 pub mod root_window;
+pub mod search_bar;
+pub mod thread_pane;
 
 #[derive(Clone, Copy, Hash, Eq, PartialEq)]
 pub enum UiTag {
     RootWindow,
     ChatSidebar,
     MessageViewer,
+    InputField,
+    ThreadPane,
+    ThreadInputField,
     InputHint,
 }
 
 pub type UiId = u16;
 
+const SPINNER: [&str; 10] = ["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"];
+
 pub struct UiMetaData {
     shoud_draw: Cell<bool>,
     current_active: Cell<UiId>,
@@ -81,6 +90,11 @@ impl UiMetaData {
         self.tag_to_id.borrow().get(tag).copied()
     }
 
+    // one spinner frame every 6 draws
+    pub fn spinner(&self) -> &'static str {
+        SPINNER[(self.draw_counter.get() / 6 % SPINNER.len() as u64) as usize]
+    }
+
     pub fn increment_draw_counter(&self) {
         match self.draw_counter.get().checked_add(1) {
             None => self.draw_counter.set(0),
@@ -95,6 +109,8 @@ impl Default for UiMetaData {
     }
 }
 
+// a result only lives until the event loop has sent its action on, boxing it would buy nothing
+#[allow(clippy::large_enum_variant)]
 pub enum TerminalEventResult {
     Handled(Action),
     NotHandled(TerminalEvent),
@@ -0,0 +1,90 @@
+/// ReactionPicker is the row of emojis the message viewer opens with + over the focused message.
+///
+/// h and l move along the row and Enter picks, the digit keys pick the emoji at their place.
+/// The emojis the user has put on the message already are marked, picking one takes it off again.
+use crossterm::event::KeyCode;
+use ratatui::prelude::*;
+use ratatui::widgets::{Block, Borders, Paragraph};
+
+use crate::models::{Message, UsrID};
+use crate::widgets::message_bubble::{ellipsize, BubbleTheme};
+
+// what the picker offers, the digit keys pick them in this order
+const REACTIONS: [&str; 8] = ["👍", "👎", "😄", "🎉", "😕", "🚀", "👀", "🔥"];
+
+// what a key pressed in the picker did
+pub(super) enum Picking {
+    Browsing,
+    Closed,
+    Picked(&'static str),
+}
+
+#[derive(Default)]
+pub(super) struct ReactionPicker {
+    // the emoji the cursor is on
+    pub(super) selected: usize,
+}
+
+impl ReactionPicker {
+    // the picker takes all the keys while it is open
+    pub(super) fn handle_key(&mut self, code: KeyCode) -> Picking {
+        match code {
+            KeyCode::Esc => return Picking::Closed,
+            KeyCode::Char('h') | KeyCode::Left => self.selected = self.selected.saturating_sub(1),
+            KeyCode::Char('l') | KeyCode::Right => {
+                self.selected = (self.selected + 1).min(REACTIONS.len() - 1)
+            }
+            KeyCode::Enter => return Picking::Picked(REACTIONS[self.selected]),
+            KeyCode::Char(c) => {
+                let picked = c
+                    .to_digit(10)
+                    .map(|d| d as usize)
+                    .filter(|d| (1..=REACTIONS.len()).contains(d));
+                if let Some(d) = picked {
+                    return Picking::Picked(REACTIONS[d - 1]);
+                }
+            }
+            _ => {}
+        }
+        Picking::Browsing
+    }
+
+    // the emojis in a row at the bottom of `area`, the ones `me` has put on `message` marked
+    pub(super) fn get_ui<'a>(
+        &self,
+        message: &Message,
+        me: &UsrID,
+        theme: &BubbleTheme,
+        area: Rect,
+    ) -> (Rect, Paragraph<'a>) {
+        let mut spans = Vec::new();
+        for (idx, emoji) in REACTIONS.iter().enumerate() {
+            let mut style = if message.reacted(emoji, me) {
+                theme.reacted
+            } else {
+                Style::default()
+            };
+            if idx == self.selected {
+                style = style.add_modifier(Modifier::REVERSED);
+            }
+            spans.push(Span::styled(format!(" {}{} ", idx + 1, emoji), style));
+        }
+        let line = Line::from(spans);
+
+        let width = (line.width() as u16 + 2).min(area.width);
+        let height = 3.min(area.height);
+        let popup = Rect {
+            x: area.x + (area.width - width) / 2,
+            y: area.y + area.height.saturating_sub(height + 1),
+            width,
+            height,
+        };
+        let paragraph = Paragraph::new(line).block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::new().fg(Color::Green))
+                .title(ellipsize(" react · ⏎ pick · esc ", width.saturating_sub(2))),
+        );
+        (popup, paragraph)
+    }
+}
@@ -10,7 +10,8 @@ use crate::tio::TerminalEvent;
 
 use super::{
     blueprints::UiBlueprints, chat_sidebar::LeftSessionList, fps_hint::FpsHint,
-    keypress_hint::KeyPressHint, message_viewer::RightSpace, TerminalEventResult, UiEntity, UiId,
+    input_field::ComposerTarget, input_field::InputField, keypress_hint::KeyPressHint,
+    message_viewer::RightSpace, thread_pane::ThreadPane, TerminalEventResult, UiEntity, UiId,
     UiMetaData, UiTag,
 };
 
@@ -20,6 +21,11 @@ pub struct RootWindow {
     tag: Option<UiTag>,
     left_session_list: LeftSessionList,
     right_space: RightSpace,
+    input_field: InputField,
+    thread_pane: ThreadPane,
+    thread_input_field: InputField,
+    // a thread is open, it takes a column next to the conversation
+    thread_open: bool,
     fps_hint: FpsHint,
     key_press_hint: KeyPressHint,
     pub meta_data: Rc<UiMetaData>,
@@ -46,6 +52,19 @@ impl RootWindow {
             .with_metadata(ret.meta_data.clone())
             .with_context_model(app)
             .with_tag(UiTag::MessageViewer);
+        ret.input_field = ret
+            .input_field
+            .with_metadata(ret.meta_data.clone())
+            .with_tag(UiTag::InputField);
+        ret.thread_pane = ret
+            .thread_pane
+            .with_metadata(ret.meta_data.clone())
+            .with_tag(UiTag::ThreadPane);
+        ret.thread_input_field = ret
+            .thread_input_field
+            .with_metadata(ret.meta_data.clone())
+            .with_target(ComposerTarget::Thread)
+            .with_tag(UiTag::ThreadInputField);
         ret.fps_hint = ret.fps_hint.with_metadata(ret.meta_data.clone());
         ret.key_press_hint = ret.key_press_hint.with_metadata(ret.meta_data.clone());
 
@@ -54,6 +73,7 @@ impl RootWindow {
 
     pub fn update_with_context_model(&mut self, app: &App) {
         self.left_session_list.update_with_context_model(app);
+        self.thread_open = app.messages_model.get_thread().is_some();
     }
 
     pub fn with_tag(self, tag: UiTag) -> Self {
@@ -81,8 +101,50 @@ impl UiEntity for RootWindow {
             .make_blueprints(chunks[0], ui_mgr, layer1);
         ui_mgr.add_new_blueprint(&self.left_session_list, chunks[0], layer1);
 
-        ui_mgr.add_new_blueprint(&self.right_space, chunks[1], layer1);
-        self.right_space.make_blueprints(chunks[1], ui_mgr, layer1);
+        // an open thread splits the conversation column in two
+        let columns = match self.thread_open {
+            true => Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(55), Constraint::Percentage(45)].as_ref())
+                .split(chunks[1]),
+            false => Rc::new([chunks[1]]),
+        };
+
+        // the composer is docked under the message viewer
+        let right_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Min(3),
+                Constraint::Length(self.input_field.height()),
+            ])
+            .split(columns[0]);
+
+        ui_mgr.add_new_blueprint(&self.right_space, right_chunks[0], layer1);
+        self.right_space
+            .make_blueprints(right_chunks[0], ui_mgr, layer1);
+
+        ui_mgr.add_new_blueprint(&self.input_field, right_chunks[1], layer1);
+        self.input_field
+            .make_blueprints(right_chunks[1], ui_mgr, layer1);
+
+        // and the thread has a composer of its own
+        if let Some(&column) = columns.get(1) {
+            let thread_chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Min(3),
+                    Constraint::Length(self.thread_input_field.height()),
+                ])
+                .split(column);
+
+            ui_mgr.add_new_blueprint(&self.thread_pane, thread_chunks[0], layer1);
+            self.thread_pane
+                .make_blueprints(thread_chunks[0], ui_mgr, layer1);
+
+            ui_mgr.add_new_blueprint(&self.thread_input_field, thread_chunks[1], layer1);
+            self.thread_input_field
+                .make_blueprints(thread_chunks[1], ui_mgr, layer1);
+        }
 
         ui_mgr.add_new_blueprint(&self.fps_hint, area, layer2);
         self.fps_hint.make_blueprints(area, ui_mgr, layer2);
@@ -98,11 +160,46 @@ impl UiEntity for RootWindow {
             TerminalEventResult::Handled(act) => return TerminalEventResult::Handled(act),
         };
 
+        // everyone hears the clock, whoever has the keyboard
+        if let TerminalEvent::Tick = proxied_evt {
+            let results = [
+                self.left_session_list
+                    .handle_terminal_event(proxied_evt, app),
+                self.right_space.handle_terminal_event(proxied_evt, app),
+                self.input_field.handle_terminal_event(proxied_evt, app),
+                self.thread_pane.handle_terminal_event(proxied_evt, app),
+                self.thread_input_field
+                    .handle_terminal_event(proxied_evt, app),
+                self.fps_hint.handle_terminal_event(proxied_evt, app),
+            ];
+            let actions = results
+                .into_iter()
+                .filter_map(|result| match result {
+                    TerminalEventResult::Handled(Action::Nop) => None,
+                    TerminalEventResult::Handled(act) => Some(act),
+                    TerminalEventResult::NotHandled(_) => None,
+                })
+                .collect::<Vec<_>>();
+            return TerminalEventResult::Handled(match actions.is_empty() {
+                true => Action::Nop,
+                false => Action::MultiAction(actions),
+            });
+        }
+
         let sub_ent_evt = match app.state_model {
             StateModel::Chats => self
                 .left_session_list
                 .handle_terminal_event(proxied_evt, app),
             StateModel::Messages => self.right_space.handle_terminal_event(proxied_evt, app),
+            StateModel::Composer => self.input_field.handle_terminal_event(proxied_evt, app),
+            // the thread may have been closed under the keyboard, e.g. by switching sessions
+            StateModel::Thread | StateModel::ThreadComposer if !self.thread_open => {
+                self.right_space.handle_terminal_event(proxied_evt, app)
+            }
+            StateModel::Thread => self.thread_pane.handle_terminal_event(proxied_evt, app),
+            StateModel::ThreadComposer => self
+                .thread_input_field
+                .handle_terminal_event(proxied_evt, app),
             StateModel::FPS => self.fps_hint.handle_terminal_event(proxied_evt, app),
         };
         // there must be best way to not depackage
@@ -0,0 +1,180 @@
+/// SearchBar is the search of the message viewer, opened with / and closed with Esc.
+///
+/// The query is typed into the bar first, Enter compiles it and looks for it among the loaded
+/// messages while the backend searches the rest of the conversation. The hits are walked through
+/// with n and N, oldest first, the replies of threads are not in the timeline and not found.
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use fancy_regex::Regex;
+
+use std::rc::Rc;
+
+use crate::models::messages::Search;
+use crate::models::{Message, MsgID, RemoteData, SearchQuery};
+
+use super::message_viewer::in_timeline;
+
+// what a key typed into the bar did
+pub(super) enum Typed {
+    Editing,
+    Closed,
+    // the query compiled, it is looked for from now on
+    Find(SearchQuery),
+    Invalid(String),
+}
+
+// the query being typed or looked for, and where the cursor is among its hits
+#[derive(Default)]
+pub(super) struct SearchBar {
+    pub(super) query: SearchQuery,
+    // the query is still being typed, the keys go to the bar
+    pub(super) typing: bool,
+    // the query compiled, once it is looked for
+    pub(super) matcher: Option<Rc<Regex>>,
+    pub(super) hits: Vec<MsgID>,
+    current: Option<MsgID>,
+    // whether the backend has searched beyond the loaded messages
+    backend: RemoteData<()>,
+}
+
+impl SearchBar {
+    // a new search keeps the toggles of the one before
+    pub(super) fn open(previous: Option<SearchBar>) -> Self {
+        let previous = previous.map(|bar| bar.query).unwrap_or_default();
+        Self {
+            query: SearchQuery {
+                text: String::new(),
+                ..previous
+            },
+            typing: true,
+            ..Default::default()
+        }
+    }
+
+    pub(super) fn title(&self) -> String {
+        if self.typing {
+            let kind = if self.query.regex { "regex" } else { "text" };
+            let case = if self.query.case_sensitive {
+                ", match case"
+            } else {
+                ""
+            };
+            return format!(
+                " /{}▏ {}{} · ⏎ find · alt-r regex · alt-c case · esc ",
+                self.query.text, kind, case
+            );
+        }
+        let position = self
+            .current
+            .as_ref()
+            .and_then(|id| self.hits.iter().position(|hit| hit == id));
+        let found = match (position, self.hits.len()) {
+            (_, 0) => String::from("no matches"),
+            (Some(idx), total) => format!("{} of {}", idx + 1, total),
+            (None, total) => format!("{} found", total),
+        };
+        let backend = match self.backend {
+            RemoteData::Failed { .. } => " · loaded messages only",
+            _ if self.backend.is_loading() => " · searching the server",
+            _ => "",
+        };
+        format!(
+            " /{} · {}{} · n older · N newer · esc ",
+            self.query.text, found, backend
+        )
+    }
+
+    // the keys while the query is typed, the bar takes them all
+    pub(super) fn type_key(&mut self, key: KeyEvent) -> Typed {
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        let alt = key.modifiers.contains(KeyModifiers::ALT);
+        match key.code {
+            KeyCode::Esc => return Typed::Closed,
+            KeyCode::Enter if self.query.text.is_empty() => return Typed::Closed,
+            KeyCode::Enter => match self.query.matcher() {
+                Ok(matcher) => {
+                    self.matcher = Some(Rc::new(matcher));
+                    self.typing = false;
+                    return Typed::Find(self.query.clone());
+                }
+                Err(err) => return Typed::Invalid(err.to_string()),
+            },
+            KeyCode::Char('r') if alt => self.query.regex = !self.query.regex,
+            KeyCode::Char('c') if alt => self.query.case_sensitive = !self.query.case_sensitive,
+            KeyCode::Backspace => {
+                self.query.text.pop();
+            }
+            KeyCode::Char(c) if !ctrl && !alt => self.query.text.push(c),
+            _ => {}
+        }
+        Typed::Editing
+    }
+
+    // the hits as the messages are now, and the newest loaded one to put the cursor on
+    // if it is not on a hit yet
+    pub(super) fn update(&mut self, messages: &[Message], found: Option<&Search>) -> Option<usize> {
+        let matcher = self.matcher.as_ref()?;
+        // what the backend found for another query is of no use
+        let found = found.filter(|search| search.query == self.query);
+        self.backend = match found.map(|search| &search.hits) {
+            Some(RemoteData::Success(_)) => RemoteData::Success(()),
+            Some(RemoteData::Failed { error, .. }) => RemoteData::Failed {
+                last_good: None,
+                error: error.clone(),
+            },
+            Some(_) => RemoteData::Pending,
+            None => RemoteData::Uninitialized,
+        };
+        let further = found
+            .and_then(|search| search.hits.data())
+            .map(|hits| hits.as_slice())
+            .unwrap_or_default();
+        self.hits = find_hits(matcher, messages, further);
+        if self.current.is_some() {
+            return None;
+        }
+        let newest = self
+            .hits
+            .last()
+            .and_then(|id| messages.iter().position(|m| m.id == *id))?;
+        self.current = Some(messages[newest].id.clone());
+        Some(newest)
+    }
+
+    // move on to the hit older or newer than the current one, or why there is none
+    pub(super) fn step(&mut self, newer: bool) -> Result<MsgID, &'static str> {
+        let position = self
+            .current
+            .as_ref()
+            .and_then(|id| self.hits.iter().position(|hit| hit == id));
+        let next = match position {
+            _ if self.hits.is_empty() => None,
+            None => Some(self.hits.len() - 1),
+            Some(idx) if newer => Some(idx + 1).filter(|&idx| idx < self.hits.len()),
+            Some(idx) => idx.checked_sub(1),
+        };
+        let Some(next) = next else {
+            return Err(match (self.hits.is_empty(), newer) {
+                (true, _) => "no messages found",
+                (false, true) => "this is the newest match",
+                (false, false) => "this is the oldest match",
+            });
+        };
+        let id = self.hits[next].clone();
+        self.current = Some(id.clone());
+        Ok(id)
+    }
+}
+
+// the messages `matcher` finds among the loaded ones and the ones the backend found,
+// oldest first, the replies of threads are not in the timeline and not found
+fn find_hits(matcher: &Regex, messages: &[Message], found: &[Message]) -> Vec<MsgID> {
+    let loaded = messages
+        .iter()
+        .filter(|m| in_timeline(m) && SearchQuery::found_in(matcher, m));
+    let further = found
+        .iter()
+        .filter(|m| in_timeline(m) && messages.iter().all(|old| old.id != m.id));
+    let mut hits = loaded.chain(further).collect::<Vec<_>>();
+    hits.sort_by_key(|m| m.sent_at);
+    hits.into_iter().map(|m| m.id.clone()).collect()
+}
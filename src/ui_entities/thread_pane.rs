@@ -0,0 +1,299 @@
+/// ThreadPane shows the thread open in a group next to the conversation.
+///
+/// The message that started it is on top, then its replies oldest first.
+/// i writes a reply in the composer docked under it, Esc closes the thread.
+use crossterm::event::{KeyCode, KeyModifiers};
+use ratatui::prelude::*;
+use ratatui::text::Text;
+use ratatui::widgets::block::{Position, Title};
+use ratatui::widgets::{
+    Block, Borders, Paragraph, Scrollbar, ScrollbarOrientation, ScrollbarState,
+};
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::action::{Action, MessagesModelAction, StateModelAction};
+use crate::app::App;
+use crate::models::state::StateModel;
+use crate::models::{Message, MsgID};
+use crate::tio::TerminalEvent;
+use crate::widgets::message_bubble::{ellipsize, BubbleSide, BubbleTheme};
+
+use super::bubble_layouts::{in_sight, BubbleLayouts, Decoration};
+use super::{TerminalEventResult, UiEntity, UiId, UiMetaData, UiTag};
+
+const HINT: &str = " i reply · esc close ";
+
+#[derive(Default)]
+struct InternalState {
+    // the thread drawn last time, another one opens at its newest reply
+    root: Option<MsgID>,
+    // the viewport sticks to the newest reply until it is scrolled away from it
+    follow: bool,
+    // as measured by the last draw
+    view_height: usize,
+    total_rows: usize,
+    vertical_scroll_state: ScrollbarState,
+    vertical_scroll: usize,
+}
+
+impl InternalState {
+    fn max_scroll(&self) -> usize {
+        self.total_rows.saturating_sub(self.view_height)
+    }
+
+    fn scroll_down(&mut self, rows: usize) {
+        self.vertical_scroll = self
+            .vertical_scroll
+            .saturating_add(rows)
+            .min(self.max_scroll());
+        self.follow = self.vertical_scroll == self.max_scroll();
+    }
+
+    fn scroll_up(&mut self, rows: usize) {
+        self.vertical_scroll = self.vertical_scroll.saturating_sub(rows);
+        self.follow = false;
+    }
+
+    // a half page, at least one row
+    fn half_page(&self) -> usize {
+        (self.view_height / 2).max(1)
+    }
+
+    // measure what is to be drawn, a new thread starts at the bottom
+    fn update(&mut self, root: Option<&MsgID>, total_rows: usize, view_height: usize) {
+        if self.root.as_ref() != root {
+            self.root = root.cloned();
+            self.follow = true;
+        }
+        self.total_rows = total_rows;
+        self.view_height = view_height;
+        if self.follow || self.vertical_scroll > self.max_scroll() {
+            self.vertical_scroll = self.max_scroll();
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct ThreadPane {
+    id: UiId,
+    tag: Option<UiTag>,
+    meta_data: Rc<UiMetaData>,
+    bubble_theme: BubbleTheme,
+    internal_state: RefCell<InternalState>,
+    // bubbles laid out by earlier draws, only the ones that changed are laid out again
+    layouts: RefCell<BubbleLayouts>,
+}
+
+impl ThreadPane {
+    pub fn with_metadata(self, meta: Rc<UiMetaData>) -> Self {
+        let mut ret = self;
+        ret.id = meta.next_id();
+        ret.meta_data = meta;
+        ret
+    }
+
+    pub fn with_tag(self, tag: UiTag) -> Self {
+        let mut ret = self;
+        ret.tag = Some(tag);
+        ret.meta_data.set_tag(tag, ret.id);
+        ret
+    }
+
+    // the message that started the thread and its replies, none if it is not loaded
+    fn thread<'a>(app: &'a App, root: &MsgID) -> Option<(&'a Message, Vec<&'a Message>)> {
+        let messages = app.messages_model.get_model_data().data().copied()?;
+        let first = messages.iter().find(|m| m.id == *root)?;
+        let replies = messages
+            .iter()
+            .filter(|m| m.thread.as_ref() == Some(root))
+            .collect();
+        Some((first, replies))
+    }
+
+    // what is in sight of the thread, and the row of the thread it starts at
+    fn get_ui_text<'a>(&self, app: &App, width: u16, view_height: usize) -> (Text<'a>, usize) {
+        let mut internal = self.internal_state.borrow_mut();
+        let root = app.messages_model.get_thread();
+        let thread = root.and_then(|root| Self::thread(app, root));
+        let Some((first, replies)) = thread else {
+            internal.update(root, 0, view_height);
+            let text = match root {
+                Some(_) => Text::from(Line::styled(
+                    "the message that started this thread is gone",
+                    Style::new().dim(),
+                )),
+                None => Text::default(),
+            };
+            return (text, 0);
+        };
+
+        let me = app.current_user();
+        let today = chrono::Local::now().date_naive();
+        let side_of = |m: &Message| {
+            if m.from == me {
+                BubbleSide::Right
+            } else {
+                BubbleSide::Left
+            }
+        };
+        let mut layouts = self.layouts.borrow_mut();
+        layouts.set_width(width);
+        if let Some(messages) = app.messages_model.get_model_data().data() {
+            layouts.retain(messages);
+        }
+
+        // the separator is drawn under the message that started the thread, it counts as one of its rows
+        let thread = std::iter::once(first).chain(replies.iter().copied());
+        let mut tops = Vec::with_capacity(replies.len() + 1);
+        let mut heights = Vec::with_capacity(replies.len() + 1);
+        let mut total = 0;
+        for (idx, m) in thread.clone().enumerate() {
+            let decoration = Decoration::of(m, &me, today);
+            let rows = layouts
+                .get(m, decoration, side_of(m), &self.bubble_theme, 0, false)
+                .lines
+                .len()
+                + usize::from(idx == 0);
+            tops.push(total);
+            heights.push(rows);
+            total += rows;
+        }
+        internal.update(root, total, view_height);
+
+        // only what is in sight gets painted
+        let range = in_sight(&tops, &heights, internal.vertical_scroll, view_height);
+        let start = tops.get(range.start).copied().unwrap_or(total);
+        let mut text = Text::default();
+        for (idx, m) in thread.enumerate().take(range.end).skip(range.start) {
+            let decoration = Decoration::of(m, &me, today);
+            let laid = layouts.get(m, decoration, side_of(m), &self.bubble_theme, 0, true);
+            text.lines.extend(laid.lines.iter().cloned());
+            if idx == 0 {
+                text.lines.push(
+                    Line::styled(separator(replies.len()), Style::new().dim())
+                        .alignment(Alignment::Center),
+                );
+            }
+        }
+        (text, start)
+    }
+}
+
+impl UiEntity for ThreadPane {
+    fn draw(&self, app: &App, frame: &mut Frame, area: Rect) {
+        let bdr_stl = match app.state_model {
+            StateModel::Thread => Style::new().fg(Color::Green),
+            _ => Style::default(),
+        };
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .border_style(bdr_stl)
+            .gray()
+            .title(Span::styled(
+                "Thread",
+                Style::default().add_modifier(Modifier::BOLD),
+            ))
+            .title(
+                Title::from(Span::styled(
+                    ellipsize(HINT, area.width.saturating_sub(2)),
+                    Style::new().dim(),
+                ))
+                .position(Position::Bottom),
+            );
+
+        let width = area.width.saturating_sub(4);
+        let view_height = area.height.saturating_sub(2) as usize;
+        let (text, start) = self.get_ui_text(app, width, view_height);
+
+        let mut internal = self.internal_state.borrow_mut();
+        let scroll = internal.vertical_scroll;
+        internal.vertical_scroll_state = internal
+            .vertical_scroll_state
+            .content_length(internal.total_rows)
+            .position(scroll);
+
+        let paragraph = Paragraph::new(text)
+            .gray()
+            .block(block)
+            .scroll(((scroll - start) as u16, 0));
+        frame.render_widget(paragraph, area);
+        frame.render_stateful_widget(
+            Scrollbar::default()
+                .orientation(ScrollbarOrientation::VerticalRight)
+                .begin_symbol(Some("↑"))
+                .end_symbol(Some("↓")),
+            area,
+            &mut internal.vertical_scroll_state,
+        );
+    }
+
+    fn handle_terminal_event(&mut self, event: TerminalEvent, _app: &App) -> TerminalEventResult {
+        let TerminalEvent::Key(key) = event else {
+            return TerminalEventResult::NotHandled(event);
+        };
+        let mut internal = self.internal_state.borrow_mut();
+        let ctrl = key.modifiers.contains(KeyModifiers::CONTROL);
+        match key.code {
+            KeyCode::Char('d') if ctrl => {
+                let rows = internal.half_page();
+                internal.scroll_down(rows);
+            }
+            KeyCode::Char('u') if ctrl => {
+                let rows = internal.half_page();
+                internal.scroll_up(rows);
+            }
+            KeyCode::Char('j') | KeyCode::Down => internal.scroll_down(1),
+            KeyCode::Char('k') | KeyCode::Up => internal.scroll_up(1),
+            KeyCode::Char('g') | KeyCode::Home => internal.scroll_up(usize::MAX),
+            KeyCode::Char('G') | KeyCode::End => internal.scroll_down(usize::MAX),
+            KeyCode::Char('i') | KeyCode::Enter => {
+                return TerminalEventResult::Handled(Action::StateModel(
+                    StateModelAction::SetActive(StateModel::ThreadComposer),
+                ));
+            }
+            KeyCode::Esc => {
+                return TerminalEventResult::Handled(Action::MultiAction(vec![
+                    Action::MessagesModel(MessagesModelAction::CloseThread),
+                    Action::StateModel(StateModelAction::SetActive(StateModel::Messages)),
+                ]));
+            }
+            _ => return TerminalEventResult::NotHandled(event),
+        }
+        TerminalEventResult::Handled(Action::Nop)
+    }
+}
+
+// the line between the message that started the thread and its replies
+fn separator(replies: usize) -> String {
+    match replies {
+        0 => String::from("no replies yet, i to write one"),
+        1 => String::from("── 1 reply ──"),
+        n => format!("── {} replies ──", n),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_threads_open_at_the_newest_reply() {
+        let (a, b) = (MsgID("a".to_string()), MsgID("b".to_string()));
+        let mut state = InternalState::default();
+        state.update(Some(&a), 30, 10);
+        assert_eq!(state.vertical_scroll, 20);
+
+        // scrolled away, a new reply does not move the viewport
+        state.scroll_up(5);
+        state.update(Some(&a), 40, 10);
+        assert_eq!(state.vertical_scroll, 15);
+        state.scroll_down(usize::MAX);
+        assert!(state.follow);
+
+        state.scroll_up(usize::MAX);
+        state.update(Some(&b), 12, 10);
+        assert_eq!(state.vertical_scroll, 2);
+    }
+}
@@ -0,0 +1,211 @@
+/// Highlight colors fenced code blocks with the grammars syntect ships with, no network needed.
+///
+/// The colors come from the terminal palette, like everything else in the app,
+/// rather than from a syntect theme with colors of its own.
+/// Grammars are loaded the first time a block needs them,
+/// and highlighted blocks are kept, since a bubble is laid out again whenever the width,
+/// the focus or the search changes, while its code stays the same.
+use ratatui::style::{Color, Modifier, Style};
+use syntect::easy::HighlightLines;
+use syntect::highlighting::{
+    Color as SyntectColor, FontStyle, ScopeSelectors, StyleModifier, Theme, ThemeItem,
+    ThemeSettings,
+};
+use syntect::parsing::SyntaxSet;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+// highlighted blocks kept around, the cache starts over once it holds this many
+const CACHE_CAPACITY: usize = 128;
+
+// scopes and how they are shown, a token takes the style of the selector matching it best
+const PALETTE: [(&str, Color, bool); 12] = [
+    ("comment", Color::DarkGray, true),
+    ("string, markup.raw", Color::Green, false),
+    (
+        "constant.numeric, constant.language, constant.character",
+        Color::Cyan,
+        false,
+    ),
+    ("keyword, storage.modifier", Color::Magenta, false),
+    (
+        "storage.type, entity.name.type, entity.name.class, support.type, support.class",
+        Color::Yellow,
+        false,
+    ),
+    (
+        "entity.name.function, support.function, variable.function",
+        Color::Blue,
+        false,
+    ),
+    (
+        "support.type.property-name, meta.mapping.key string",
+        Color::Blue,
+        false,
+    ),
+    ("entity.name.tag", Color::Red, false),
+    ("entity.other.attribute-name", Color::Yellow, false),
+    (
+        "variable.parameter, variable.other.readwrite",
+        Color::Reset,
+        false,
+    ),
+    ("markup.heading, markup.bold", Color::Reset, false),
+    ("invalid", Color::Red, false),
+];
+
+/// Lines of styled runs, one per line of code
+pub type Highlighted = Vec<Vec<(String, Style)>>;
+
+thread_local! {
+    static CACHE: RefCell<HashMap<(String, Vec<String>), Highlighted>> = RefCell::new(HashMap::new());
+}
+
+fn syntaxes() -> &'static (SyntaxSet, Theme) {
+    static SYNTAXES: OnceLock<(SyntaxSet, Theme)> = OnceLock::new();
+    SYNTAXES.get_or_init(|| (SyntaxSet::load_defaults_newlines(), palette_theme()))
+}
+
+// syntect themes hold rgb colors, a palette index is smuggled in the red channel instead,
+// with alpha 0 standing for the default color of the terminal
+fn palette_theme() -> Theme {
+    let scopes = PALETTE
+        .iter()
+        .filter_map(|(scope, color, italic)| {
+            Some(ThemeItem {
+                scope: ScopeSelectors::from_str(scope).ok()?,
+                style: StyleModifier {
+                    foreground: Some(encode(*color)),
+                    background: None,
+                    font_style: italic.then_some(FontStyle::ITALIC),
+                },
+            })
+        })
+        .collect();
+    Theme {
+        settings: ThemeSettings {
+            foreground: Some(encode(Color::Reset)),
+            ..Default::default()
+        },
+        scopes,
+        ..Default::default()
+    }
+}
+
+const ANSI: [Color; 16] = [
+    Color::Black,
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::Gray,
+    Color::DarkGray,
+    Color::LightRed,
+    Color::LightGreen,
+    Color::LightYellow,
+    Color::LightBlue,
+    Color::LightMagenta,
+    Color::LightCyan,
+    Color::White,
+];
+
+fn encode(color: Color) -> SyntectColor {
+    match ANSI.iter().position(|c| *c == color) {
+        Some(idx) => SyntectColor {
+            r: idx as u8,
+            g: 0,
+            b: 0,
+            a: 1,
+        },
+        None => SyntectColor {
+            r: 0,
+            g: 0,
+            b: 0,
+            a: 0,
+        },
+    }
+}
+
+fn decode(style: syntect::highlighting::Style) -> Style {
+    let mut ret = Style::default();
+    if style.foreground.a != 0 {
+        ret = ret.fg(ANSI[style.foreground.r as usize % ANSI.len()]);
+    }
+    if style.font_style.contains(FontStyle::ITALIC) {
+        ret = ret.add_modifier(Modifier::ITALIC);
+    }
+    ret
+}
+
+/// Highlight `lines` as code in `lang`, which is a language name or a file extension.
+///
+/// Returns none for a language there is no grammar for, the block is shown as plain text then.
+pub fn highlight(lang: &str, lines: &[String]) -> Option<Highlighted> {
+    let key = (lang.to_string(), lines.to_vec());
+    if let Some(hit) = CACHE.with(|cache| cache.borrow().get(&key).cloned()) {
+        return Some(hit);
+    }
+
+    let (syntaxes, theme) = syntaxes();
+    let syntax = syntaxes.find_syntax_by_token(lang)?;
+    let mut highlighter = HighlightLines::new(syntax, theme);
+    let mut highlighted = Vec::new();
+    for line in lines {
+        // the grammars expect every line to end with a newline
+        let line = format!("{}\n", line);
+        let runs = highlighter.highlight_line(&line, syntaxes).ok()?;
+        highlighted.push(
+            runs.into_iter()
+                .map(|(style, text)| (text.trim_end_matches('\n').to_string(), decode(style)))
+                .filter(|(text, _)| !text.is_empty())
+                .collect(),
+        );
+    }
+
+    CACHE.with(|cache| {
+        let mut cache = cache.borrow_mut();
+        if cache.len() >= CACHE_CAPACITY {
+            cache.clear();
+        }
+        cache.insert(key, highlighted.clone());
+    });
+    Some(highlighted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_highlight_known_and_unknown_languages() {
+        let code = vec![
+            "fn main() {".to_string(),
+            "    let answer = 42; // why".to_string(),
+            "}".to_string(),
+        ];
+        let lines = highlight("rust", &code).unwrap();
+        let text = lines
+            .iter()
+            .map(|line| line.iter().map(|(t, _)| t.as_str()).collect::<String>())
+            .collect::<Vec<_>>();
+        assert_eq!(text, code);
+        let style_of = |word: &str| {
+            lines[1]
+                .iter()
+                .find(|(text, _)| text.contains(word))
+                .map(|(_, style)| *style)
+                .unwrap()
+        };
+        assert_eq!(style_of("42").fg, Some(Color::Cyan));
+        assert_eq!(style_of("why").fg, Some(Color::DarkGray));
+        assert!(style_of("why").add_modifier.contains(Modifier::ITALIC));
+
+        assert!(highlight("json", &["{\"a\": 1}".to_string()]).is_some());
+        assert!(highlight("no-such-language", &code).is_none());
+    }
+}
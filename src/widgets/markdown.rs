@@ -0,0 +1,322 @@
+/// Markdown is the small subset of markdown message bodies are rendered with.
+///
+/// A body is split into blocks, lines of text and fenced code blocks,
+/// and a line of text is split into runs of the same emphasis:
+/// `**bold**`, `*italic*` or `_italic_`, `~~strike~~`, `` `code` ``, `[links](url)` and bare urls.
+/// Anything that does not parse is kept as it was typed.
+const FENCE: &str = "```";
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Block {
+    Line(String),
+    Code {
+        lang: Option<String>,
+        lines: Vec<String>,
+    },
+}
+
+/// How a run of text is shown.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Emphasis {
+    pub bold: bool,
+    pub italic: bool,
+    pub strike: bool,
+    pub code: bool,
+    pub link: bool,
+    // the target of a link, shown after its text
+    pub url: bool,
+}
+
+/// Split a body into lines and fenced code blocks, a fence left open runs to the end.
+pub fn blocks(content: &str) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut code: Option<(Option<String>, Vec<String>)> = None;
+    for line in content.lines() {
+        let fence = line.trim_start().strip_prefix(FENCE);
+        code = match (code, fence) {
+            (None, Some(lang)) => {
+                let lang = Some(lang.trim().to_string()).filter(|l| !l.is_empty());
+                Some((lang, Vec::new()))
+            }
+            (Some((lang, lines)), Some(rest)) if rest.trim().is_empty() => {
+                blocks.push(Block::Code { lang, lines });
+                None
+            }
+            (Some((lang, mut lines)), _) => {
+                lines.push(line.to_string());
+                Some((lang, lines))
+            }
+            (None, None) => {
+                blocks.push(Block::Line(line.to_string()));
+                None
+            }
+        };
+    }
+    if let Some((lang, lines)) = code {
+        blocks.push(Block::Code { lang, lines });
+    }
+    blocks
+}
+
+/// The first line of text of a body with the markup taken out, e.g. to quote the body in a reply.
+///
+/// Code is only quoted if there is nothing else, whitespace is squeezed into single spaces.
+pub fn excerpt(content: &str) -> String {
+    let blocks = blocks(content);
+    let text = blocks.iter().find_map(|block| match block {
+        Block::Line(line) if !line.trim().is_empty() => Some(line.as_str()),
+        _ => None,
+    });
+    let Some(line) = text else {
+        let code = blocks.iter().find_map(|block| match block {
+            Block::Code { lines, .. } => lines.iter().find(|line| !line.trim().is_empty()),
+            _ => None,
+        });
+        return squeeze(code.map(String::as_str).unwrap_or_default());
+    };
+    let line = list_item(line).map_or(line, |(_, _, rest)| rest);
+    let text = inline_runs(line)
+        .into_iter()
+        .filter(|(_, emphasis)| !emphasis.url)
+        .map(|(text, _)| text)
+        .collect::<String>();
+    squeeze(&text)
+}
+
+fn squeeze(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Split the start of a list item off a line.
+///
+/// Returns the indentation, the marker to show (a bullet or the number) and the rest of the line.
+pub fn list_item(line: &str) -> Option<(&str, String, &str)> {
+    let rest = line.trim_start_matches(' ');
+    let indent = &line[..line.len() - rest.len()];
+    let (marker, text) = rest.split_once(' ')?;
+    let marker = match marker {
+        "-" | "*" | "+" => String::from("•"),
+        _ => {
+            let number = marker.strip_suffix(['.', ')'])?;
+            if number.is_empty() || !number.chars().all(|c| c.is_ascii_digit()) {
+                return None;
+            }
+            marker.to_string()
+        }
+    };
+    Some((indent, marker, text.trim_start_matches(' ')))
+}
+
+/// Split a line into runs of text and the emphasis they are shown with.
+///
+/// A marker only counts if it has a match on the same line and hugs the text it wraps,
+/// `_` also has to stand at a word boundary so snake_case names are left alone,
+/// and a backslash keeps the char after it as it is.
+pub fn inline_runs(line: &str) -> Vec<(String, Emphasis)> {
+    let mut runs = Vec::new();
+    parse_inline(line, Emphasis::default(), &mut runs);
+    runs
+}
+
+fn parse_inline(line: &str, emphasis: Emphasis, runs: &mut Vec<(String, Emphasis)>) {
+    let mut plain = String::new();
+    let mut rest = line;
+    let flush = |plain: &mut String, runs: &mut Vec<(String, Emphasis)>| {
+        if !plain.is_empty() {
+            runs.push((std::mem::take(plain), emphasis));
+        }
+    };
+    while let Some(ch) = rest.chars().next() {
+        let prev = line[..line.len() - rest.len()].chars().last();
+
+        if ch == '\\' {
+            if let Some(escaped) = rest[1..]
+                .chars()
+                .next()
+                .filter(|c| c.is_ascii_punctuation())
+            {
+                plain.push(escaped);
+                rest = &rest[1 + escaped.len_utf8()..];
+                continue;
+            }
+        }
+
+        // code is taken as it is, nothing inside of it is markup
+        if ch == '`' {
+            if let Some(close) = rest[1..].find('`').filter(|idx| *idx > 0) {
+                flush(&mut plain, runs);
+                let code = Emphasis {
+                    code: true,
+                    ..emphasis
+                };
+                runs.push((rest[1..1 + close].to_string(), code));
+                rest = &rest[close + 2..];
+                continue;
+            }
+        }
+
+        if ch == '[' {
+            if let Some((text, url, len)) = link(rest) {
+                flush(&mut plain, runs);
+                let link = Emphasis {
+                    link: true,
+                    ..emphasis
+                };
+                parse_inline(text, link, runs);
+                if url != text {
+                    let url_emphasis = Emphasis { url: true, ..link };
+                    runs.push((format!(" ({})", url), url_emphasis));
+                }
+                rest = &rest[len..];
+                continue;
+            }
+        }
+
+        if !prev.is_some_and(char::is_alphanumeric)
+            && (rest.starts_with("https://") || rest.starts_with("http://"))
+        {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            // punctuation right after a url most likely ends the sentence
+            let url = rest[..end].trim_end_matches(['.', ',', ';', ':', '!', '?', ')']);
+            flush(&mut plain, runs);
+            let link = Emphasis {
+                link: true,
+                ..emphasis
+            };
+            runs.push((url.to_string(), link));
+            rest = &rest[url.len()..];
+            continue;
+        }
+
+        if let Some((marker, close)) = delimited(rest, prev) {
+            flush(&mut plain, runs);
+            let inner = match marker {
+                "**" => Emphasis {
+                    bold: true,
+                    ..emphasis
+                },
+                "~~" => Emphasis {
+                    strike: true,
+                    ..emphasis
+                },
+                _ => Emphasis {
+                    italic: true,
+                    ..emphasis
+                },
+            };
+            parse_inline(&rest[marker.len()..marker.len() + close], inner, runs);
+            rest = &rest[marker.len() * 2 + close..];
+            continue;
+        }
+
+        plain.push(ch);
+        rest = &rest[ch.len_utf8()..];
+    }
+    flush(&mut plain, runs);
+}
+
+// `[text](url)` at the start of `rest`, with the length it takes
+fn link(rest: &str) -> Option<(&str, &str, usize)> {
+    let text_end = rest.find("](")?;
+    let text = &rest[1..text_end];
+    let url_start = text_end + 2;
+    let url_len = rest[url_start..].find(')')?;
+    let url = &rest[url_start..url_start + url_len];
+    if text.is_empty() || url.is_empty() || url.contains(char::is_whitespace) {
+        return None;
+    }
+    Some((text, url, url_start + url_len + 1))
+}
+
+// an emphasis marker at the start of `rest` and where its match is, counted after the marker
+fn delimited(rest: &str, prev: Option<char>) -> Option<(&'static str, usize)> {
+    ["**", "~~", "*", "_"]
+        .into_iter()
+        .filter(|marker| rest.starts_with(marker))
+        .find_map(|marker| {
+            let inner = &rest[marker.len()..];
+            if inner.starts_with(char::is_whitespace) || inner.starts_with(marker) {
+                return None;
+            }
+            if marker == "_" && prev.is_some_and(char::is_alphanumeric) {
+                return None;
+            }
+            let close = inner
+                .match_indices(marker)
+                .map(|(idx, _)| idx)
+                .find(|&idx| {
+                    let before = inner[..idx].chars().last();
+                    let after = inner[idx + marker.len()..].chars().next();
+                    idx > 0
+                        && !before.is_some_and(char::is_whitespace)
+                        && !(marker == "_" && after.is_some_and(char::is_alphanumeric))
+                })?;
+            Some((marker, close))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn texts_with(runs: &[(String, Emphasis)], pick: fn(&Emphasis) -> bool) -> Vec<&str> {
+        runs.iter()
+            .filter(|(_, e)| pick(e))
+            .map(|(text, _)| text.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_inline_runs() {
+        let runs = inline_runs(
+            "**bold** *it* ~~gone~~ `a*b*c` [docs](https://x.io) see https://y.io. \\*not* snake_case_name",
+        );
+        assert_eq!(texts_with(&runs, |e| e.bold), vec!["bold"]);
+        assert_eq!(texts_with(&runs, |e| e.italic), vec!["it"]);
+        assert_eq!(texts_with(&runs, |e| e.strike), vec!["gone"]);
+        assert_eq!(texts_with(&runs, |e| e.code), vec!["a*b*c"]);
+        assert_eq!(
+            texts_with(&runs, |e| e.link && !e.url),
+            vec!["docs", "https://y.io"]
+        );
+        assert_eq!(texts_with(&runs, |e| e.url), vec![" (https://x.io)"]);
+        let text = runs.iter().map(|(t, _)| t.as_str()).collect::<String>();
+        assert!(text.ends_with(". *not* snake_case_name"));
+    }
+
+    #[test]
+    fn test_blocks_and_lists() {
+        let content = "look:\n```rust\nfn main() {}\n\n```\n- one\n  12. two\n-not a list";
+        assert_eq!(
+            blocks(content),
+            vec![
+                Block::Line("look:".to_string()),
+                Block::Code {
+                    lang: Some("rust".to_string()),
+                    lines: vec!["fn main() {}".to_string(), String::new()],
+                },
+                Block::Line("- one".to_string()),
+                Block::Line("  12. two".to_string()),
+                Block::Line("-not a list".to_string()),
+            ]
+        );
+        assert_eq!(list_item("- one"), Some(("", "•".to_string(), "one")));
+        assert_eq!(
+            list_item("  12. two"),
+            Some(("  ", "12.".to_string(), "two"))
+        );
+        assert_eq!(list_item("-not a list"), None);
+        assert_eq!(list_item("2021 was"), None);
+    }
+
+    #[test]
+    fn test_excerpt() {
+        assert_eq!(
+            excerpt("\n- see **the**  [docs](https://x.io)\nmore"),
+            "see the docs"
+        );
+        assert_eq!(excerpt("```rust\n\n  let a = 1;\n```"), "let a = 1;");
+        assert_eq!(excerpt("```\nlet a = 1;\n```\nlooks off"), "looks off");
+        assert_eq!(excerpt(""), "");
+    }
+}
@@ -1,9 +1,20 @@
-use ratatui::text::{Line, Text};
+use fancy_regex::Regex;
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::{Line, Span, Text};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthChar;
+
+use std::rc::Rc;
+
+use super::highlight;
+use super::markdown::{self, Block, Emphasis};
 
 const COLON_WIDTH: u16 = 1;
 const COLOUMN_WIDTH: u16 = 1;
 const PROMPT_UP_CORNER: char = '╔';
 const PROMPT_DOWN_CORNER: char = '╚';
+const PROMPT_UP_CORNER_RIGHT: char = '╗';
+const PROMPT_DOWN_CORNER_RIGHT: char = '╝';
 const PROMPT_HORIZON: char = '═';
 const COLON: char = ':';
 const FRAME_LEFTUP: char = '╭';
@@ -13,6 +24,121 @@ const FRAME_RIGTHDOWN: char = '╯';
 const FRAME_HORIZON: char = '─';
 const FRAME_VERT: char = '│';
 
+const CODE_LEFTUP: char = '┌';
+const CODE_RIGHTUP: char = '┐';
+const CODE_LEFTDOWN: char = '└';
+const CODE_RIGHTDOWN: char = '┘';
+const CODE_HORIZON: char = '─';
+const CODE_VERT: char = '│';
+
+const TAB_WIDTH: usize = 4;
+
+const QUOTE_BAR: char = '▍';
+const ELLIPSIS: char = '…';
+// a quote is cut off at the width of the reply, but a very short reply still shows this much of it
+const QUOTE_MIN_WIDTH: u16 = 24;
+
+// authors get one of these, picked by their id
+const AUTHOR_PALETTE: [Color; 10] = [
+    Color::Red,
+    Color::Green,
+    Color::Yellow,
+    Color::Blue,
+    Color::Magenta,
+    Color::Cyan,
+    Color::LightRed,
+    Color::LightGreen,
+    Color::LightBlue,
+    Color::LightMagenta,
+];
+
+// the painting is a grid of terminal cells, each cell holds one grapheme and its style,
+// a wide grapheme takes two cells and the second one is left empty
+type Cell = (String, Style);
+
+// one grapheme of the content, with its display width and style
+#[derive(Clone, Debug)]
+struct Glyph {
+    grapheme: String,
+    width: u16,
+    style: Style,
+}
+
+/// Which side of the bubble the author prompt is drawn on.
+///
+/// Messages of others are drawn on the left, the user's own ones mirrored on the right.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BubbleSide {
+    #[default]
+    Left,
+    Right,
+}
+
+/// Styles a bubble is drawn with.
+///
+/// The author is drawn in a color of its own unless `author` is set.
+#[derive(Clone, Debug)]
+pub struct BubbleTheme {
+    pub prompt: Style,
+    pub frame: Style,
+    pub body: Style,
+    pub code: Style,
+    pub link: Style,
+    // the excerpt of the message a reply answers
+    pub quote: Style,
+    // how many replies there are in the thread started by the message
+    pub thread: Style,
+    // the reactions under the bubble, and the ones the user put there
+    pub reaction: Style,
+    pub reacted: Style,
+    // what the bubble tells about the message itself, when it was sent, edited or deleted
+    pub note: Style,
+    // the text matching the search
+    pub found: Style,
+    // the frame and prompt of the bubble the cursor is on
+    pub focus: Style,
+    pub author: Option<Style>,
+}
+
+impl Default for BubbleTheme {
+    fn default() -> Self {
+        Self {
+            prompt: Style::new().add_modifier(Modifier::DIM),
+            frame: Style::new().add_modifier(Modifier::DIM),
+            body: Style::default(),
+            code: Style::new().fg(Color::Yellow),
+            link: Style::new()
+                .fg(Color::Blue)
+                .add_modifier(Modifier::UNDERLINED),
+            quote: Style::new().add_modifier(Modifier::DIM | Modifier::ITALIC),
+            thread: Style::new().fg(Color::Cyan),
+            reaction: Style::new().add_modifier(Modifier::DIM),
+            reacted: Style::new().fg(Color::Yellow).add_modifier(Modifier::BOLD),
+            note: Style::new().add_modifier(Modifier::DIM | Modifier::ITALIC),
+            found: Style::new().fg(Color::Black).bg(Color::Yellow),
+            focus: Style::new().fg(Color::Green).add_modifier(Modifier::BOLD),
+            author: None,
+        }
+    }
+}
+
+/// The message a reply answers, quoted in one line above the body of the reply.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Quote {
+    Message { author: String, excerpt: String },
+    // the original is not at hand, e.g. it is older than anything loaded
+    Missing,
+}
+
+/// One emoji put on a message, shown in a row under the bubble with how many put it there.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ReactionCount {
+    pub emoji: String,
+    pub count: usize,
+    // the user is one of them
+    pub mine: bool,
+}
+
 pub struct MessageBubble {
     content: String,
     author: String,
@@ -22,12 +148,34 @@ pub struct MessageBubble {
     max_width: u16,
     shift_width: u16,
     margin_width: u16, // default to 1
-    painting: Vec<Vec<char>>,
+    side: BubbleSide,
+    theme: BubbleTheme,
+    focused: bool,
+    quote: Option<Quote>,
+    // replies in the thread started by this message, told in the bottom of the frame
+    replies: usize,
+    reactions: Vec<ReactionCount>,
+    // when the message was last edited, told in the bottom right of the frame
+    edited: Option<String>,
+    // the message was deleted, only the frame is left
+    tombstone: bool,
+    // when the message was sent, told next to the author
+    time: Option<String>,
+    // the message follows one of the same author shortly before, it is drawn without the prompt
+    continued: bool,
+    // what the search is looking for, highlighted in the body
+    highlight: Option<Rc<Regex>>,
+    // how far code blocks are scrolled to the right, and how far they could be
+    code_scroll: u16,
+    code_overflow: u16,
+    // the content split into lines of styled graphemes
+    lines: Vec<Vec<Glyph>>,
+    painting: Vec<Vec<Cell>>,
 }
 
 impl MessageBubble {
     pub fn new(max_width: u16, message: &str, author: &str) -> Self {
-        let mut bbl = MessageBubble {
+        MessageBubble {
             bubble_width: 0,
             bubble_height: 0,
             content: message.to_owned(),
@@ -36,84 +184,479 @@ impl MessageBubble {
             max_width,
             shift_width: 4,
             margin_width: 1,
+            side: BubbleSide::default(),
+            theme: BubbleTheme::default(),
+            focused: false,
+            quote: None,
+            replies: 0,
+            reactions: Vec::new(),
+            edited: None,
+            tombstone: false,
+            time: None,
+            continued: false,
+            highlight: None,
+            code_scroll: 0,
+            code_overflow: 0,
+            lines: Vec::new(),
             painting: Vec::new(),
-        };
-        bbl.init_width();
-        bbl.init_height();
-        bbl.init_painting();
-        bbl
+        }
+    }
+
+    pub fn with_side(self, side: BubbleSide) -> Self {
+        let mut ret = self;
+        ret.side = side;
+        ret
+    }
+
+    pub fn with_theme(self, theme: BubbleTheme) -> Self {
+        let mut ret = self;
+        ret.theme = theme;
+        ret
+    }
+
+    pub fn with_focus(self, focused: bool) -> Self {
+        let mut ret = self;
+        ret.focused = focused;
+        ret
+    }
+
+    pub fn with_quote(self, quote: Option<Quote>) -> Self {
+        let mut ret = self;
+        ret.quote = quote;
+        ret
+    }
+
+    pub fn with_replies(self, replies: usize) -> Self {
+        let mut ret = self;
+        ret.replies = replies;
+        ret
+    }
+
+    pub fn with_reactions(self, reactions: Vec<ReactionCount>) -> Self {
+        let mut ret = self;
+        ret.reactions = reactions;
+        ret
+    }
+
+    /// Mark the message as edited at `at`, which is shown as it is given
+    pub fn with_edited(self, at: Option<String>) -> Self {
+        let mut ret = self;
+        ret.edited = at;
+        ret
+    }
+
+    pub fn with_tombstone(self, tombstone: bool) -> Self {
+        let mut ret = self;
+        ret.tombstone = tombstone;
+        ret
+    }
+
+    /// Tell when the message was sent next to the author, `at` is shown as it is given
+    pub fn with_time(self, at: Option<String>) -> Self {
+        let mut ret = self;
+        ret.time = at;
+        ret
+    }
+
+    pub fn with_continued(self, continued: bool) -> Self {
+        let mut ret = self;
+        ret.continued = continued;
+        ret
+    }
+
+    /// Highlight the text `found` matches, line by line as the body is shown
+    pub fn with_highlight(self, found: Option<Rc<Regex>>) -> Self {
+        let mut ret = self;
+        ret.highlight = found;
+        ret
+    }
+
+    pub fn with_code_scroll(self, scroll: u16) -> Self {
+        let mut ret = self;
+        ret.code_scroll = scroll;
+        ret
+    }
+
+    /// How many cells the widest code block can be scrolled to the right, known once painted
+    pub fn code_overflow(&self) -> u16 {
+        self.code_overflow
     }
 
+    /// Lay the bubble out as the builders left it, then paint it
     pub fn finish_painting(&mut self) {
+        self.layout();
         self.draw_prompt();
         self.draw_bubble_frame();
         self.draw_message_content();
+        self.draw_reactions();
     }
 
     fn init_height(&mut self) {
-        let content_len = self.content.len() as u16; // truncate long messages?
-        let message_height = content_len / self.message_width
-            + if content_len % self.message_width == 0 {
-                0
+        let message_height = self.lines.len() as u16;
+        let reactions_height = u16::from(!self.reactions.is_empty());
+        self.bubble_height = self.prompt_height() + 1 + message_height + 1 + reactions_height;
+    }
+
+    // the bubble is as wide as its longest line, but never wider than `max_width`
+    fn init_width(&mut self) {
+        let decoration = COLOUMN_WIDTH * 2 + self.margin_width * 2 + self.shift_width;
+        let available = self.max_width.saturating_sub(decoration).max(1);
+        self.code_overflow = 0;
+        self.lines = match self.tombstone {
+            true => vec![glyphs("message deleted", self.theme.note)],
+            false => self.layout_body(available),
+        };
+        self.highlight_body();
+        let body_width = self.lines.iter().map(|line| line_width(line)).max();
+        let quote_width = body_width.unwrap_or(0).max(QUOTE_MIN_WIDTH).min(available);
+        if let Some(quote) = self.layout_quote(quote_width) {
+            self.lines.insert(0, quote);
+        }
+        self.message_width = self
+            .lines
+            .iter()
+            .map(|line| line_width(line))
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        // the frame is made wide enough for the footer and the edit mark between its corners,
+        // with at least one stroke of the frame between them
+        let label_width = |label: Option<String>| {
+            label.map(|label| line_width(&glyphs(&label, Style::default())))
+        };
+        let labels_width = match (label_width(self.footer()), label_width(self.edit_mark())) {
+            (Some(footer), Some(mark)) => footer + mark,
+            (Some(label), None) | (None, Some(label)) => label.saturating_sub(1),
+            (None, None) => 0,
+        };
+        self.message_width = self.message_width.max(labels_width);
+        // and the reactions fit under it
+        let reactions_width = line_width(&self.reaction_row());
+        self.message_width = self.message_width.max(reactions_width);
+        self.bubble_width = decoration + self.message_width;
+    }
+
+    fn layout(&mut self) {
+        self.init_width();
+        self.init_height();
+        self.init_painting();
+    }
+
+    // the body in lines of at most `width` cells, hard line breaks and blank lines are kept
+    fn layout_body(&mut self, width: u16) -> Vec<Vec<Glyph>> {
+        let mut lines = Vec::new();
+        for block in markdown::blocks(&self.content) {
+            match block {
+                Block::Line(line) => lines.extend(self.layout_line(&line, width)),
+                Block::Code { lang, lines: code } => {
+                    lines.extend(self.layout_code(lang.as_deref(), &code, width))
+                }
+            }
+        }
+        if lines.is_empty() {
+            lines.push(Vec::new());
+        }
+        lines
+    }
+
+    // a match is looked for in each line as it is shown, so one broken by wrapping is not found
+    fn highlight_body(&mut self) {
+        let Some(ref found) = self.highlight else {
+            return;
+        };
+        if self.tombstone {
+            return;
+        }
+        for line in self.lines.iter_mut() {
+            let text = line.iter().map(|g| g.grapheme.as_str()).collect::<String>();
+            let matched = found
+                .find_iter(&text)
+                .flatten()
+                .filter(|m| !m.range().is_empty())
+                .map(|m| m.range())
+                .collect::<Vec<_>>();
+            let mut offset = 0;
+            for glyph in line.iter_mut() {
+                let range = offset..offset + glyph.grapheme.len();
+                offset = range.end;
+                if matched
+                    .iter()
+                    .any(|m| m.start < range.end && range.start < m.end)
+                {
+                    glyph.style = glyph.style.patch(self.theme.found);
+                }
+            }
+        }
+    }
+
+    // the quote of the message a reply answers, cut off to fit in one line
+    fn layout_quote(&self, width: u16) -> Option<Vec<Glyph>> {
+        let mut line = glyphs(&format!("{} ", QUOTE_BAR), self.theme.frame);
+        match self.quote.as_ref()? {
+            Quote::Message { author, excerpt } => {
+                line.extend(glyphs(author, self.author_style(author)));
+                line.push(space(Style::default()));
+                line.extend(glyphs(excerpt, self.theme.quote));
+            }
+            Quote::Missing => line.extend(glyphs("message not loaded", self.theme.quote)),
+        }
+        Some(truncate(line, width, self.theme.quote))
+    }
+
+    // a line keeps its indentation when wrapped, the text of a list item is lined up after its marker
+    fn layout_line(&self, line: &str, width: u16) -> Vec<Vec<Glyph>> {
+        let line = line.replace('\t', &" ".repeat(TAB_WIDTH));
+        let (prefix, text) = match markdown::list_item(&line) {
+            Some((indent, marker, text)) => (format!("{}{} ", indent, marker), text),
+            None => {
+                let text = line.trim_start_matches(' ');
+                (line[..line.len() - text.len()].to_string(), text)
+            }
+        };
+        let hanging = line_width(&glyphs(&prefix, Style::default()));
+        let runs = std::iter::once((prefix, Style::default()))
+            .chain(
+                markdown::inline_runs(text)
+                    .into_iter()
+                    .map(|(text, emphasis)| (text, self.emphasis_style(emphasis))),
+            )
+            .collect::<Vec<_>>();
+        wrap_runs(&runs, width, hanging)
+    }
+
+    // a fenced block is boxed and never wrapped, lines too long for the box are scrolled instead
+    fn layout_code(&mut self, lang: Option<&str>, code: &[String], width: u16) -> Vec<Vec<Glyph>> {
+        let frame = self.theme.frame;
+        let code = code
+            .iter()
+            .map(|line| line.replace('\t', &" ".repeat(TAB_WIDTH)))
+            .collect::<Vec<_>>();
+        // a language without a grammar is shown in the plain code style
+        let code = match lang.and_then(|lang| highlight::highlight(lang, &code)) {
+            Some(lines) => lines
+                .iter()
+                .map(|runs| {
+                    runs.iter()
+                        .flat_map(|(text, style)| glyphs(text, *style))
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>(),
+            None => code
+                .iter()
+                .map(|line| glyphs(line, self.theme.code))
+                .collect::<Vec<_>>(),
+        };
+        let longest = code.iter().map(|line| line_width(line)).max().unwrap_or(0);
+        let inner = longest.min(width.saturating_sub(4)).max(1);
+        let overflow = longest - longest.min(inner);
+        self.code_overflow = self.code_overflow.max(overflow);
+        let scroll = self.code_scroll.min(overflow);
+
+        let lang = lang.map(|lang| format!(" {} ", lang));
+        let mut lines = vec![rule(
+            CODE_LEFTUP,
+            lang.as_deref(),
+            CODE_RIGHTUP,
+            inner + 4,
+            frame,
+        )];
+        for line in code {
+            let mut row = glyphs(&format!("{} ", CODE_VERT), frame);
+            row.extend(visible(&line, scroll, inner));
+            row.extend(glyphs(&format!(" {}", CODE_VERT), frame));
+            lines.push(row);
+        }
+        let hint = (overflow > 0).then_some(" h/l ⇆ ");
+        lines.push(rule(CODE_LEFTDOWN, hint, CODE_RIGHTDOWN, inner + 4, frame));
+        lines
+    }
+
+    fn emphasis_style(&self, emphasis: Emphasis) -> Style {
+        let mut style = Style::default();
+        if emphasis.bold {
+            style = style.add_modifier(Modifier::BOLD);
+        }
+        if emphasis.italic {
+            style = style.add_modifier(Modifier::ITALIC);
+        }
+        if emphasis.strike {
+            style = style.add_modifier(Modifier::CROSSED_OUT);
+        }
+        if emphasis.code {
+            style = style.patch(self.theme.code);
+        }
+        if emphasis.link {
+            style = style.patch(self.theme.link);
+        }
+        if emphasis.url {
+            style = style.add_modifier(Modifier::DIM);
+        }
+        style
+    }
+
+    fn footer(&self) -> Option<String> {
+        match self.replies {
+            0 => None,
+            1 => Some(String::from(" 1 reply ")),
+            n => Some(format!(" {} replies ", n)),
+        }
+    }
+
+    // the reactions lined up with the body, two spaces apart
+    fn reaction_row(&self) -> Vec<Glyph> {
+        let mut row = Vec::new();
+        for (idx, reaction) in self.reactions.iter().enumerate() {
+            if idx > 0 {
+                row.extend(glyphs("  ", Style::default()));
+            }
+            let style = if reaction.mine {
+                self.theme.reacted
             } else {
-                1
+                self.theme.reaction
             };
-        self.bubble_height = 2 + message_height + 1;
+            row.extend(glyphs(
+                &format!("{} {}", reaction.emoji, reaction.count),
+                style,
+            ));
+        }
+        row
     }
 
-    fn init_width(&mut self) {
-        self.message_width = self.content.len() as u16;
-        self.bubble_width =
-            COLOUMN_WIDTH * 2 + self.message_width + self.margin_width * 2 + self.shift_width;
-        self.bubble_width = std::cmp::min(self.bubble_width, self.max_width);
-        self.message_width =
-            self.bubble_width - self.shift_width - self.margin_width * 2 - COLOUMN_WIDTH * 2;
+    // the row the prompt takes above the frame, none for a continued message
+    fn prompt_height(&self) -> u16 {
+        u16::from(!self.continued)
+    }
+
+    fn edit_mark(&self) -> Option<String> {
+        self.edited.as_ref().map(|at| format!(" (edited {}) ", at))
     }
 
     fn init_painting(&mut self) {
         let height = self.bubble_height as usize;
         let width = self.bubble_width as usize;
-        self.painting = vec![vec![' '; width]; height];
+        self.painting = vec![vec![(String::from(" "), Style::default()); width]; height];
+    }
+
+    // put a grapheme at (row, col), returns the column after it
+    fn paint(&mut self, row: usize, col: usize, grapheme: &str, width: u16, style: Style) -> usize {
+        let cells = &mut self.painting[row];
+        cells[col] = (grapheme.to_string(), style);
+        for i in 1..width as usize {
+            cells[col + i] = (String::new(), style);
+        }
+        col + width as usize
+    }
+
+    fn paint_char(&mut self, row: usize, col: usize, ch: char, style: Style) {
+        self.painting[row][col] = (ch.to_string(), style);
+    }
+
+    fn author_style(&self, author: &str) -> Style {
+        self.theme.author.unwrap_or_else(|| {
+            Style::new()
+                .fg(author_color(author))
+                .add_modifier(Modifier::BOLD)
+        })
+    }
+
+    // the frame of a focused bubble stands out
+    fn frame_style(&self) -> Style {
+        if self.focused {
+            self.theme.focus
+        } else {
+            self.theme.frame
+        }
     }
 
     fn draw_prompt(&mut self) {
-        self.painting[0][0] = PROMPT_UP_CORNER;
-        for i in 1..self.shift_width {
-            self.painting[0][i as usize] = PROMPT_HORIZON;
+        if self.continued {
+            return;
         }
+        let prompt = if self.focused {
+            self.theme.focus
+        } else {
+            self.theme.prompt
+        };
+        let author_style = self.author_style(&self.author);
 
-        for i in 0..self.author.len() + 2 {
-            if self.shift_width as usize + i >= self.painting[0].len() {
-                break;
-            }
+        // the name is cut off where the bubble ends, a wide grapheme is not split in half
+        let room = (self.bubble_width - self.shift_width) as usize;
+        let author = self.author.clone();
+        let mut used = 0;
+        let name = std::iter::once(("<", 1, prompt))
+            .chain(
+                author
+                    .graphemes(true)
+                    .map(|g| (g, grapheme_width(g), author_style)),
+            )
+            .chain(std::iter::once((">", 1, prompt)))
+            .take_while(|(_, width, _)| {
+                used += *width as usize;
+                used <= room
+            })
+            .collect::<Vec<_>>();
+        let mut name_width = name
+            .iter()
+            .map(|(_, width, _)| *width as usize)
+            .sum::<usize>();
+        // the time goes on the outer side of the name, if there is room left for it
+        let time = self
+            .time
+            .as_deref()
+            .map(|at| glyphs(at, self.theme.note))
+            .filter(|time| name_width + 1 + line_width(time) as usize <= room)
+            .unwrap_or_default();
+        let time = time
+            .iter()
+            .map(|glyph| (glyph.grapheme.as_str(), glyph.width, glyph.style))
+            .collect::<Vec<_>>();
+        let name = match (time.is_empty(), self.side) {
+            (true, _) => name,
+            (false, BubbleSide::Left) => [name, vec![(" ", 1, prompt)], time].concat(),
+            (false, BubbleSide::Right) => [time, vec![(" ", 1, prompt)], name].concat(),
+        };
+        name_width = name.iter().map(|(_, width, _)| *width as usize).sum();
 
-            let ch = if i == 0 {
-                '<'
-            } else if i == self.author.len() + 1 {
-                '>'
-            } else {
-                self.author.chars().nth(i - 1).unwrap()
-            };
-            self.painting[0][self.shift_width as usize + i] = ch;
+        // the prompt sits on the left of the bubble, or mirrored on its right
+        let (mut col, prompt_start) = match self.side {
+            BubbleSide::Left => (self.shift_width as usize, 0),
+            BubbleSide::Right => (room - name_width, room),
+        };
+        for (g, width, style) in name {
+            col = self.paint(0, col, g, width, style);
         }
 
         for i in 0..self.shift_width {
-            let ch = if i == 0 {
-                PROMPT_DOWN_CORNER
-            } else if i == self.shift_width - 1 {
-                COLON
-            } else {
-                PROMPT_HORIZON
+            let (up, down) = match self.side {
+                BubbleSide::Left if i == 0 => (PROMPT_UP_CORNER, PROMPT_DOWN_CORNER),
+                BubbleSide::Left if i == self.shift_width - COLON_WIDTH => (PROMPT_HORIZON, COLON),
+                BubbleSide::Right if i == self.shift_width - 1 => {
+                    (PROMPT_UP_CORNER_RIGHT, PROMPT_DOWN_CORNER_RIGHT)
+                }
+                BubbleSide::Right if i < COLON_WIDTH => (PROMPT_HORIZON, COLON),
+                _ => (PROMPT_HORIZON, PROMPT_HORIZON),
             };
-            self.painting[1][i as usize] = ch;
+            let col = prompt_start + i as usize;
+            self.paint_char(0, col, up, prompt);
+            self.paint_char(1, col, down, prompt);
+        }
+    }
+
+    // the first column of the frame
+    fn frame_offset(&self) -> u16 {
+        match self.side {
+            BubbleSide::Left => self.shift_width,
+            BubbleSide::Right => 0,
         }
     }
 
     fn draw_bubble_frame(&mut self) {
-        let row_offset = 1;
-        let col_offset = self.shift_width;
+        let style = self.frame_style();
+        let row_offset = self.prompt_height();
+        let col_offset = self.frame_offset();
         let frame_width = self.bubble_width - self.shift_width;
-        let frame_height = self.bubble_height - 1;
+        // the row of reactions is below the frame
+        let frame_height = self.bubble_height - row_offset - u16::from(!self.reactions.is_empty());
 
         for col in 0..frame_width {
             let ch = if col == 0 {
@@ -123,7 +666,7 @@ impl MessageBubble {
             } else {
                 FRAME_HORIZON
             };
-            self.painting[row_offset as usize][(col + col_offset) as usize] = ch;
+            self.paint_char(row_offset as usize, (col + col_offset) as usize, ch, style);
         }
 
         for col in 0..frame_width {
@@ -134,64 +677,549 @@ impl MessageBubble {
             } else {
                 FRAME_HORIZON
             };
-            self.painting[(row_offset + frame_height - 1) as usize][(col + col_offset) as usize] =
-                ch;
+            self.paint_char(
+                (row_offset + frame_height - 1) as usize,
+                (col + col_offset) as usize,
+                ch,
+                style,
+            );
+        }
+
+        if let Some(footer) = self.footer() {
+            let row = (row_offset + frame_height - 1) as usize;
+            let mut col = (col_offset + 2) as usize;
+            for glyph in glyphs(&footer, self.theme.thread) {
+                col = self.paint(row, col, &glyph.grapheme, glyph.width, glyph.style);
+            }
+        }
+
+        if let Some(mark) = self.edit_mark() {
+            let row = (row_offset + frame_height - 1) as usize;
+            let mark = glyphs(&mark, self.theme.note);
+            let mut col = (col_offset + frame_width - 1 - line_width(&mark)) as usize;
+            for glyph in mark {
+                col = self.paint(row, col, &glyph.grapheme, glyph.width, glyph.style);
+            }
         }
 
         for row in 1..frame_height - 1 {
-            self.painting[(row + row_offset) as usize][col_offset as usize] = FRAME_VERT;
-            self.painting[(row + row_offset) as usize][(frame_width - 1 + col_offset) as usize] =
-                FRAME_VERT;
+            let row = (row + row_offset) as usize;
+            self.paint_char(row, col_offset as usize, FRAME_VERT, style);
+            self.paint_char(
+                row,
+                (frame_width - 1 + col_offset) as usize,
+                FRAME_VERT,
+                style,
+            );
         }
     }
 
     fn draw_message_content(&mut self) {
-        let row_offset = 2;
-        let col_offset = self.shift_width + 1 + 1;
-        for (ith, ch) in self.content.chars().enumerate() {
-            let row = ith as u16 / self.message_width;
-            let col = ith as u16 % self.message_width;
-            self.painting[(row + row_offset) as usize][(col + col_offset) as usize] = ch;
+        let row_offset = self.prompt_height() as usize + 1;
+        let col_offset = (self.frame_offset() + COLOUMN_WIDTH + self.margin_width) as usize;
+        let lines = std::mem::take(&mut self.lines);
+        for (row, line) in lines.iter().enumerate() {
+            let mut col = col_offset;
+            for glyph in line {
+                col = self.paint(
+                    row + row_offset,
+                    col,
+                    &glyph.grapheme,
+                    glyph.width,
+                    self.theme.body.patch(glyph.style),
+                );
+            }
+        }
+        self.lines = lines;
+    }
+
+    fn draw_reactions(&mut self) {
+        if self.reactions.is_empty() {
+            return;
+        }
+        let row = (self.bubble_height - 1) as usize;
+        let mut col = (self.frame_offset() + COLOUMN_WIDTH + self.margin_width) as usize;
+        for glyph in self.reaction_row() {
+            col = self.paint(row, col, &glyph.grapheme, glyph.width, glyph.style);
+        }
+    }
+}
+
+/// How many terminal cells a grapheme cluster takes.
+///
+/// A cluster is as wide as its widest char, so combining marks and zero width joiners add nothing,
+/// e.g. a family emoji joined from several people still takes two cells, as in most terminals.
+/// The emoji variation selector and flags (a pair of regional indicators) make it two cells wide.
+pub fn grapheme_width(grapheme: &str) -> u16 {
+    let is_regional = |c: char| ('\u{1F1E6}'..='\u{1F1FF}').contains(&c);
+    if grapheme.contains('\u{FE0F}') || grapheme.chars().filter(|c| is_regional(*c)).count() == 2 {
+        return 2;
+    }
+    grapheme
+        .chars()
+        .map(|c| c.width().unwrap_or(0))
+        .max()
+        .unwrap_or(0) as u16
+}
+
+/// `text` cut off to `width` cells, with an ellipsis where it was cut.
+pub fn ellipsize(text: &str, width: u16) -> String {
+    truncate(glyphs(text, Style::default()), width, Style::default())
+        .into_iter()
+        .map(|glyph| glyph.grapheme)
+        .collect()
+}
+
+/// Color of an author, the same id always gets the same one.
+pub fn author_color(author: &str) -> Color {
+    // FNV-1a, it does not change between runs or builds like the std hasher may
+    let hash = author.bytes().fold(0xcbf29ce484222325_u64, |hash, b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    });
+    AUTHOR_PALETTE[(hash % AUTHOR_PALETTE.len() as u64) as usize]
+}
+
+fn line_width(line: &[Glyph]) -> u16 {
+    line.iter().map(|glyph| glyph.width).sum()
+}
+
+// the graphemes of `text`, control chars have no width of their own and are shown as a space
+fn glyphs(text: &str, style: Style) -> Vec<Glyph> {
+    text.graphemes(true)
+        .map(|g| {
+            if g.chars().all(char::is_control) {
+                space(style)
+            } else {
+                Glyph {
+                    grapheme: g.to_string(),
+                    width: grapheme_width(g),
+                    style,
+                }
+            }
+        })
+        .filter(|glyph| glyph.width > 0)
+        .collect()
+}
+
+fn space(style: Style) -> Glyph {
+    Glyph {
+        grapheme: String::from(" "),
+        width: 1,
+        style,
+    }
+}
+
+// `line` cut off to `width` cells, with an ellipsis where it was cut
+fn truncate(line: Vec<Glyph>, width: u16, style: Style) -> Vec<Glyph> {
+    if line_width(&line) <= width {
+        return line;
+    }
+    if width == 0 {
+        return Vec::new();
+    }
+    let mut used = 0;
+    let mut cut = line
+        .into_iter()
+        .take_while(|glyph| {
+            used += glyph.width;
+            used < width
+        })
+        .collect::<Vec<_>>();
+    cut.extend(glyphs(&ELLIPSIS.to_string(), style));
+    cut
+}
+
+// a horizontal rule of a code box, `label` is put right after its left corner if it fits
+fn rule(left: char, label: Option<&str>, right: char, width: u16, style: Style) -> Vec<Glyph> {
+    let mut line = glyphs(&left.to_string(), style);
+    if let Some(label) = label {
+        let label = glyphs(&format!("{}{}", CODE_HORIZON, label), style);
+        if line_width(&label) + 2 <= width {
+            line.extend(label);
+        }
+    }
+    while line_width(&line) + 1 < width {
+        line.push(Glyph {
+            grapheme: CODE_HORIZON.to_string(),
+            width: 1,
+            style,
+        });
+    }
+    line.extend(glyphs(&right.to_string(), style));
+    line
+}
+
+// the `width` cells of `line` from `scroll` on, a wide grapheme cut by an edge is left blank
+fn visible(line: &[Glyph], scroll: u16, width: u16) -> Vec<Glyph> {
+    let mut shown = Vec::new();
+    let mut col = 0;
+    for glyph in line {
+        let end = col + glyph.width;
+        if col >= scroll && end <= scroll + width {
+            shown.push(glyph.clone());
+        } else if end > scroll && col < scroll + width {
+            for _ in col.max(scroll)..end.min(scroll + width) {
+                shown.push(space(glyph.style));
+            }
         }
+        col = end;
     }
+    while line_width(&shown) < width {
+        shown.push(space(Style::default()));
+    }
+    shown
 }
 
-// impl<'a> Into<Text<'a>> for MessageBubble {
-//     fn into(self) -> Text<'a> {
-//         Text::from(
-//             self.painting
-//                 .iter()
-//                 .map(|row| Line::from(row.iter().collect::<String>()))
-//                 .collect::<Vec<Line>>(),
-//         )
-//     }
-// }
+/// Break a line of styled runs into lines of at most `width` cells.
+///
+/// Lines are broken between words, the spaces at the break are dropped,
+/// and the continuation lines are indented by `hanging` cells, as long as that leaves room;
+/// a word longer than a whole line is broken between its graphemes.
+fn wrap_runs(runs: &[(String, Style)], width: u16, hanging: u16) -> Vec<Vec<Glyph>> {
+    // words are found in the text without markup, each byte of it remembers its style
+    let text = runs
+        .iter()
+        .map(|(text, _)| text.as_str())
+        .collect::<String>();
+    let styles = runs
+        .iter()
+        .flat_map(|(text, style)| std::iter::repeat_n(*style, text.len()))
+        .collect::<Vec<_>>();
+
+    let indent = if hanging * 2 <= width { hanging } else { 0 };
+    let continuation = || vec![space(Style::default()); indent as usize];
 
+    let mut lines = vec![Vec::new()];
+    for (offset, word) in text.split_word_bound_indices() {
+        let word_glyphs = word
+            .grapheme_indices(true)
+            .flat_map(|(idx, g)| glyphs(g, styles[offset + idx]))
+            .collect::<Vec<_>>();
+        let word_width = line_width(&word_glyphs);
+        let is_space = word.chars().all(char::is_whitespace);
+
+        let wrapped = lines.len() > 1;
+        let line = lines.last_mut().unwrap();
+        let used = line_width(line);
+        if is_space && wrapped && used == indent {
+            // the spaces the line was broken at
+            continue;
+        } else if used + word_width <= width {
+            line.extend(word_glyphs);
+        } else if is_space {
+            lines.push(continuation());
+        } else if indent + word_width <= width {
+            lines.push(continuation());
+            lines.last_mut().unwrap().extend(word_glyphs);
+        } else {
+            // too long for any line, fill up what is left and go on with the next ones
+            for glyph in word_glyphs {
+                if line_width(lines.last().unwrap()) + glyph.width > width {
+                    lines.push(continuation());
+                }
+                lines.last_mut().unwrap().push(glyph);
+            }
+        }
+    }
+    // spaces right before a break would only make the bubble wider
+    for line in lines.iter_mut() {
+        while line.last().is_some_and(|glyph| glyph.grapheme == " ") {
+            line.pop();
+        }
+    }
+    if lines.len() > 1 && lines.last().is_some_and(|l| l.is_empty()) {
+        lines.pop();
+    }
+    lines
+}
+
+// neighbouring cells of the same style are drawn as one span
 impl<'a> From<MessageBubble> for Text<'a> {
     fn from(value: MessageBubble) -> Self {
         Text::from(
             value
                 .painting
-                .iter()
-                .map(|row| Line::from(row.iter().collect::<String>()))
+                .into_iter()
+                .map(|row| {
+                    let mut spans: Vec<Span> = Vec::new();
+                    for (grapheme, style) in row {
+                        match spans.last_mut() {
+                            Some(span) if span.style == style => {
+                                span.content.to_mut().push_str(&grapheme)
+                            }
+                            _ => spans.push(Span::styled(grapheme, style)),
+                        }
+                    }
+                    Line::from(spans)
+                })
                 .collect::<Vec<Line>>(),
         )
     }
 }
 
+#[cfg(test)]
+fn painted_rows(bbl: &mut MessageBubble) -> Vec<String> {
+    bbl.finish_painting();
+    bbl.painting
+        .iter()
+        .map(|row| row.iter().map(|(g, _)| g.as_str()).collect())
+        .collect()
+}
+
 #[test]
 fn test_draw_prompt() {
-    let mut bbl = MessageBubble::new(
-        20, // "hello world hello world hellow world hello world,",
-        "hello", "kevin",
-    );
-    bbl.draw_prompt();
-    bbl.draw_bubble_frame();
-    bbl.draw_message_content();
-    for i in 0..bbl.painting.len() {
-        for j in 0..bbl.painting[i].len() {
-            print!("{}", bbl.painting[i][j]);
-        }
-        println!();
+    let rows = painted_rows(&mut MessageBubble::new(20, "hello", "kevin"));
+    let expected = [
+        "╔═══<kevin>  ",
+        "╚══:╭───────╮",
+        "    │ hello │",
+        "    ╰───────╯",
+    ];
+    assert_eq!(rows, expected);
+}
+
+#[test]
+fn test_wrap_at_word_boundaries() {
+    let rows = painted_rows(&mut MessageBubble::new(
+        50,
+        "Almost heaven, West Virginia. Blue ridge mountains, Shenandoah river. \
+         Life is old here, older than the trees, younger than the mountains, \
+         growing like a breeze.",
+        "Kevin Wang",
+    ));
+    let expected = [
+        "╔═══<Kevin Wang>",
+        "╚══:╭──────────────────────────────────────────╮",
+        "    │ Almost heaven, West Virginia. Blue ridge │",
+        "    │ mountains, Shenandoah river. Life is old │",
+        "    │ here, older than the trees, younger than │",
+        "    │ the mountains, growing like a breeze.    │",
+        "    ╰──────────────────────────────────────────╯",
+    ];
+    assert_eq!(rows[0].trim_end(), expected[0]);
+    assert_eq!(rows[1..], expected[1..]);
+}
+
+#[test]
+fn test_wide_graphemes_keep_the_frame_aligned() {
+    use unicode_width::UnicodeWidthStr;
+
+    let messages = [
+        "你好，世界！今天天气很好，我们去公园散步吧。",
+        "Grüße aus Zürich, café crème à l'été",
+        "family 👨‍👩‍👧 flag 🇸🇪 heart ❤️ party 🎉🎉🎉",
+        "e\u{301}e\u{301}e\u{301} combining accents",
+    ];
+    for message in messages {
+        let rows = painted_rows(&mut MessageBubble::new(24, message, "李雷"));
+        let frame_width = rows[1].width();
+        assert!(frame_width <= 24, "{:?} is too wide", rows[1]);
+        for row in &rows[1..] {
+            assert_eq!(row.width(), frame_width, "{:?} is misaligned", row);
+        }
     }
 }
+
+#[test]
+fn test_hard_breaks_blank_lines_and_indentation() {
+    let rows = painted_rows(&mut MessageBubble::new(
+        22,
+        "Traceback:\n\n  File main.py at line 3\n\tboom()\r\nfin",
+        "k",
+    ));
+    let expected = [
+        "╚══:╭────────────────╮",
+        "    │ Traceback:     │",
+        "    │                │",
+        "    │   File main.py │",
+        "    │   at line 3    │",
+        "    │     boom()     │",
+        "    │ fin            │",
+        "    ╰────────────────╯",
+    ];
+    assert_eq!(rows[1..], expected);
+}
+
+#[test]
+fn test_long_word_is_broken() {
+    let rows = painted_rows(&mut MessageBubble::new(14, "abcdefghijkl", "k"));
+    assert_eq!(rows[2], "    │ abcdef │");
+    assert_eq!(rows[3], "    │ ghijkl │");
+}
+
+#[test]
+fn test_styled_spans() {
+    let mut bbl = MessageBubble::new(60, "say **hello** to *snake_case* and snake_case", "kevin");
+    bbl.finish_painting();
+    let text = Text::from(bbl);
+    let body = &text.lines[2].spans;
+    let styled = |modifier| {
+        body.iter()
+            .filter(|span| span.style.add_modifier.contains(modifier))
+            .map(|span| span.content.as_ref())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(styled(Modifier::BOLD), vec!["hello"]);
+    assert_eq!(styled(Modifier::ITALIC), vec!["snake_case"]);
+    assert!(text.lines[2]
+        .spans
+        .iter()
+        .any(|span| span.content.contains("and snake_case")));
+
+    let author = text.lines[0]
+        .spans
+        .iter()
+        .find(|span| span.content == "kevin")
+        .unwrap();
+    assert_eq!(author.style.fg, Some(author_color("kevin")));
+    assert!(text.lines[1].spans[0]
+        .style
+        .add_modifier
+        .contains(Modifier::DIM));
+}
+
+#[test]
+fn test_right_side_is_mirrored() {
+    let mut bbl = MessageBubble::new(30, "hello there", "kevin").with_side(BubbleSide::Right);
+    let rows = painted_rows(&mut bbl);
+    let expected = [
+        "        <kevin>═══╗",
+        "╭─────────────╮:══╝",
+        "│ hello there │    ",
+        "╰─────────────╯    ",
+    ];
+    assert_eq!(rows, expected);
+}
+
+#[test]
+fn test_lists_and_code_boxes() {
+    let message = "steps:\n- run it **twice** to be sure\n```sh\ncargo run --release\n```";
+    let rows = painted_rows(&mut MessageBubble::new(30, message, "k"));
+    let expected = [
+        "╚══:╭────────────────────────╮",
+        "    │ steps:                 │",
+        "    │ • run it twice to be   │",
+        "    │   sure                 │",
+        "    │ ┌─ sh ───────────────┐ │",
+        "    │ │ cargo run --releas │ │",
+        "    │ └─ h/l ⇆ ────────────┘ │",
+        "    ╰────────────────────────╯",
+    ];
+    assert_eq!(rows[1..], expected);
+
+    let mut bbl = MessageBubble::new(30, message, "k").with_code_scroll(100);
+    let rows = painted_rows(&mut bbl);
+    assert_eq!(bbl.code_overflow(), 1);
+    assert_eq!(rows[6], "    │ │ argo run --release │ │");
+}
+
+#[test]
+fn test_replies_quote_their_parent() {
+    let quote = Quote::Message {
+        author: String::from("alice"),
+        excerpt: String::from("are we still on for friday night?"),
+    };
+    let mut bbl = MessageBubble::new(40, "sure", "kevin").with_quote(Some(quote));
+    let rows = painted_rows(&mut bbl);
+    let expected = [
+        "╔═══<kevin>                     ",
+        "╚══:╭──────────────────────────╮",
+        "    │ ▍ alice are we still on… │",
+        "    │ sure                     │",
+        "    ╰──────────────────────────╯",
+    ];
+    assert_eq!(rows, expected);
+
+    let mut bbl =
+        MessageBubble::new(60, "what was that about", "kevin").with_quote(Some(Quote::Missing));
+    assert_eq!(painted_rows(&mut bbl)[2], "    │ ▍ message not loaded │");
+}
+
+#[test]
+fn test_thread_roots_tell_their_replies() {
+    let mut bbl = MessageBubble::new(40, "lunch?", "neil").with_replies(12);
+    let rows = painted_rows(&mut bbl);
+    let expected = [
+        "╔═══<neil>         ",
+        "╚══:╭─────────────╮",
+        "    │ lunch?      │",
+        "    ╰─ 12 replies ╯",
+    ];
+    assert_eq!(rows, expected);
+}
+
+#[test]
+fn test_reactions_sit_under_the_bubble() {
+    let reactions = vec![
+        ReactionCount {
+            emoji: String::from("👍"),
+            count: 3,
+            mine: true,
+        },
+        ReactionCount {
+            emoji: String::from("🎉"),
+            count: 1,
+            mine: false,
+        },
+    ];
+    let mut bbl = MessageBubble::new(40, "ok", "neil").with_reactions(reactions);
+    let rows = painted_rows(&mut bbl);
+    let expected = [
+        "╔═══<neil>        ",
+        "╚══:╭────────────╮",
+        "    │ ok         │",
+        "    ╰────────────╯",
+        "      👍 3  🎉 1  ",
+    ];
+    assert_eq!(rows, expected);
+    assert_eq!(bbl.painting[4][6].1, BubbleTheme::default().reacted);
+    assert_eq!(bbl.painting[4][12].1, BubbleTheme::default().reaction);
+}
+
+#[test]
+fn test_edited_and_deleted_messages() {
+    let mut edited = MessageBubble::new(40, "lunch?", "neil")
+        .with_replies(2)
+        .with_edited(Some(String::from("12:30")));
+    let expected = [
+        "╔═══<neil>                         ",
+        "╚══:╭─────────────────────────────╮",
+        "    │ lunch?                      │",
+        "    ╰─ 2 replies ─ (edited 12:30) ╯",
+    ];
+    assert_eq!(painted_rows(&mut edited), expected);
+
+    let mut deleted = MessageBubble::new(40, "", "neil").with_tombstone(true);
+    assert_eq!(painted_rows(&mut deleted)[2], "    │ message deleted │");
+}
+
+#[test]
+fn test_time_and_continued_messages() {
+    let at = || Some(String::from("09:41"));
+    let mut left = MessageBubble::new(30, "hello there", "neil").with_time(at());
+    assert_eq!(painted_rows(&mut left)[0], "╔═══<neil> 09:41   ");
+    let mut right = MessageBubble::new(30, "hello there", "kevin")
+        .with_side(BubbleSide::Right)
+        .with_time(at());
+    assert_eq!(painted_rows(&mut right)[0], "  09:41 <kevin>═══╗");
+
+    // no prompt over a message that goes on from the one before
+    let mut continued = MessageBubble::new(30, "and more", "neil")
+        .with_time(at())
+        .with_continued(true);
+    let expected = ["    ╭──────────╮", "    │ and more │", "    ╰──────────╯"];
+    assert_eq!(painted_rows(&mut continued), expected);
+}
+
+#[test]
+fn test_search_matches_are_highlighted() {
+    let found = fancy_regex::Regex::new("(?i)lunch").ok().map(Rc::new);
+    let mut bbl = MessageBubble::new(40, "Lunch? **lunch** at noon", "neil").with_highlight(found);
+    bbl.finish_painting();
+    let highlighted = bbl.painting[2]
+        .iter()
+        .filter(|(_, style)| style.bg == Some(Color::Yellow))
+        .map(|(g, _)| g.as_str())
+        .collect::<String>();
+    assert_eq!(highlighted, "Lunchlunch");
+}
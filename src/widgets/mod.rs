@@ -1,2 +1,4 @@
+pub mod highlight;
 pub mod hilight_area;
+pub mod markdown;
 pub mod message_bubble;